@@ -0,0 +1,41 @@
+//! Compares borrowed reads against cloning every result, as an owned-only `eval` would.
+//! Run with `cargo bench`.
+
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+
+use eval_lifetimes::eval::{Eval, TalkObject, TalkObjectProxy, TalkValue};
+use eval_lifetimes::parser::parse;
+
+const ITERATIONS: u32 = 10_000;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let mut inventory = TalkObject::new();
+    let items = (0..1_000).map(|i| TalkValue::new_str(&format!("item #{}", i))).collect();
+    inventory.set("items", TalkValue::List(items)).unwrap();
+    let mut context = TalkObject::new();
+    context.set("inventory", TalkValue::Object(Box::new(inventory))).unwrap();
+    context.set("ready", TalkValue::Bool(true)).unwrap();
+
+    let expr = parse("ready ? inventory.items : null").unwrap();
+    assert!(matches!(expr.eval(&mut context).unwrap(), Cow::Borrowed(_)));
+
+    let borrowed = time(|| {
+        let value = expr.eval(&mut context).unwrap();
+        assert!(!value.is_null());
+    });
+    let owned = time(|| {
+        let value = expr.eval(&mut context).map(Cow::into_owned).unwrap();
+        assert!(!value.is_null());
+    });
+    println!("read 1000-item list, borrowed: {:?}/iter", borrowed);
+    println!("read 1000-item list, owned:    {:?}/iter", owned);
+}
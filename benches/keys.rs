@@ -0,0 +1,50 @@
+//! Counts the allocations made setting the same few fields on many objects. Compare
+//! `cargo bench --bench keys` against `cargo bench --bench keys --features intern-keys`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use eval_lifetimes::eval::{TalkObject, TalkObjectProxy, TalkValue};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) { System.dealloc(ptr, layout) }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const OBJECTS: usize = 10_000;
+const KEYS: [&str; 4] = ["hp", "mp", "name", "level"];
+
+fn populate() -> Vec<TalkObject<'static>> {
+    (0..OBJECTS).map(|i| {
+        let mut o = TalkObject::new();
+        for key in KEYS.iter() {
+            o.set(key, TalkValue::Int(i as i64)).unwrap();
+        }
+        o
+    }).collect()
+}
+
+fn main() {
+    // Warm up, so that an interning pool is already filled.
+    drop(populate());
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let objects = populate();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(objects.len(), OBJECTS);
+
+    let mode = if cfg!(feature = "intern-keys") { "interned" } else { "owned" };
+    println!("{} objects x {} keys, {} keys: {} allocations ({:.1}/object)",
+             OBJECTS, KEYS.len(), mode, allocations, allocations as f64 / OBJECTS as f64);
+}
@@ -0,0 +1,25 @@
+//! Reads Talk lines from stdin and prints each result. State carries over between lines.
+//!
+//! cargo run --example repl
+
+use std::io::{self, BufRead, Write};
+
+use eval_lifetimes::session::Session;
+
+fn main() -> io::Result<()> {
+    let mut session = Session::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    loop {
+        write!(stdout, "> ")?;
+        stdout.flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match session.eval_line(line.trim_end()) {
+            Ok(value) => writeln!(stdout, "{}", value)?,
+            Err(e) => writeln!(stdout, "{}", e)?,
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! World state behind async storage, such as a database or a networked save service.
+//!
+//! Evaluation itself stays synchronous. Instead, `eval_async` resolves ahead: it fetches
+//! every field a script may touch into a scope frame of the context, evaluates, and writes
+//! back the fields whose values changed. `block_on` runs that from synchronous code without a runtime.
+//!
+//! A sync proxy can't stand in for the async source during evaluation: a pushed proxy stays
+//! borrowed for the context's whole lifetime, so its changes could never be flushed after.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use crate::eval::{Eval, Expression, TalkEvalError, TalkObject, TalkValue};
+use crate::visit::Visitor;
+
+/// The async counterpart of `TalkObjectProxy`. Values are passed by value, since they
+/// can't be borrowed across an `await` from storage that isn't in memory.
+// The futures need not be `Send`: evaluation runs on the thread that awaits them.
+#[allow(async_fn_in_trait)]
+pub trait AsyncTalkObjectProxy<'world> {
+    async fn get(&mut self, name: &str) -> Option<TalkValue<'world>>;
+    async fn set(&mut self, name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError>;
+}
+
+/// Fields fetched by `prefetch`, as they were when fetched. Their frame stays on the
+/// context until `write_back` or `discard` pops it.
+#[derive(Debug)]
+pub struct Prefetch<'world> {
+    fetched: Vec<(String, TalkValue<'world>)>,
+}
+
+/// Binds the `names` that `source` has in a new scope frame of `context`, shadowing its
+/// fields and proxies without touching them. Assignments to those names update the frame.
+pub async fn prefetch<'world, P: AsyncTalkObjectProxy<'world>>(
+    source: &mut P,
+    context: &mut TalkObject<'world>,
+    names: impl IntoIterator<Item = String>,
+) -> Prefetch<'world> {
+    let mut fetched = Vec::new();
+    for name in names {
+        if let Some(val) = source.get(&name).await {
+            fetched.push((name, val));
+        }
+    }
+    let scopes = context.scopes();
+    scopes.push();
+    for (name, val) in &fetched {
+        scopes.bind(name, val.clone());
+    }
+    Prefetch { fetched }
+}
+
+impl<'world> Prefetch<'world> {
+    /// Pops the frame and writes the fetched fields whose values changed in it back to
+    /// `source`. Fields the source didn't have stay local, as they would with a sync proxy.
+    pub async fn write_back<P: AsyncTalkObjectProxy<'world>>(self, source: &mut P, context: &mut TalkObject<'world>) -> Result<(), TalkEvalError> {
+        let mut frame = context.scopes().pop().unwrap_or_default();
+        for (name, old) in self.fetched {
+            match frame.remove(&name) {
+                Some(new) if new != old => source.set(&name, new).await?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Pops the frame without writing anything back.
+    pub fn discard(self, context: &mut TalkObject<'world>) { context.scopes().pop(); }
+}
+
+/// Evaluates `expr` as if `source` were one of the context's proxies: fetches every name
+/// `expr` mentions, evaluates, and writes back what changed. Nothing is written back if
+/// evaluation fails.
+pub async fn eval_async<'world, P: AsyncTalkObjectProxy<'world>>(
+    expr: &Expression,
+    context: &mut TalkObject<'world>,
+    source: &mut P,
+) -> Result<TalkValue<'world>, TalkEvalError> {
+    let mut names = Identifiers::default();
+    names.visit_expression(expr);
+    let fetched = prefetch(source, context, names.0).await;
+    match expr.eval(context).map(Cow::into_owned) {
+        Ok(value) => fetched.write_back(source, context).await.map(|()| value),
+        Err(e) => {
+            fetched.discard(context);
+            Err(e)
+        }
+    }
+}
+
+/// Every identifier in a tree, read or written. Only top-level names can come from the
+/// source; fields below them are fetched along with their object.
+#[derive(Default)]
+struct Identifiers(BTreeSet<String>);
+
+impl Visitor for Identifiers {
+    fn visit_identifier(&mut self, name: &str) { self.0.insert(name.to_string()); }
+}
+
+/// Runs a future to completion on the current thread, parking it while the future waits.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) { self.0.unpark() }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            // Spurious unparks only cost an extra poll.
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use crate::async_proxy::{block_on, eval_async, AsyncTalkObjectProxy};
+    use crate::eval::{TalkEvalError, TalkObject, TalkObjectProxy, TalkValue};
+    use crate::parser::parse;
+
+    /// Pending once, like storage that answers later.
+    struct Later(bool);
+
+    impl Future for Later {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[derive(Default)]
+    struct Store {
+        saved: HashMap<String, i64>,
+        writes: usize,
+    }
+
+    impl<'world> AsyncTalkObjectProxy<'world> for Store {
+        async fn get(&mut self, name: &str) -> Option<TalkValue<'world>> {
+            Later(false).await;
+            self.saved.get(name).map(|i| TalkValue::Int(*i))
+        }
+
+        async fn set(&mut self, name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+            Later(false).await;
+            self.writes += 1;
+            self.saved.insert(name.to_string(), val.as_int().ok_or_else(|| val.mismatch("int"))?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_eval_async() -> Result<(), TalkEvalError> {
+        let mut store = Store::default();
+        store.saved.insert("gold".to_string(), 10);
+        store.saved.insert("level".to_string(), 3);
+        let mut context = TalkObject::new();
+
+        let expr = parse("level > 2 ? gold = gold + 5 : gold")?;
+        assert_eq!(block_on(eval_async(&expr, &mut context, &mut store))?, TalkValue::Int(15));
+        assert_eq!(store.saved["gold"], 15);
+        // `level` was read but not changed.
+        assert_eq!(store.writes, 1);
+        // The fetched fields were only ever bound in a frame that is gone now.
+        assert_eq!(context.local_len(), 0);
+        assert_eq!(context.scopes().depth(), 0);
+
+        // Fields the store doesn't have stay local.
+        let expr = parse("bonus = level * 2")?;
+        assert_eq!(block_on(eval_async(&expr, &mut context, &mut store))?, TalkValue::Int(6));
+        assert!(!store.saved.contains_key("bonus"));
+        assert_eq!(*context.get("bonus").unwrap(), TalkValue::Int(6));
+
+        // A failed write back leaves the context as it was, and so does a failed evaluation.
+        let expr = parse("gold = \"lots\"")?;
+        assert_eq!(block_on(eval_async(&expr, &mut context, &mut store)).unwrap_err().text(), "expected int, got string");
+        let expr = parse("{ gold = 0; gold / gold }")?;
+        assert_eq!(block_on(eval_async(&expr, &mut context, &mut store)).unwrap_err().text(), "Division by zero");
+        assert_eq!(store.saved["gold"], 15);
+        assert_eq!(context.local_len(), 1);
+        assert!(context.get("gold").is_none());
+        assert_eq!(context.scopes().depth(), 0);
+
+        // Observers and freezing don't get in the way, and frames pushed before stay.
+        context.on_set(|name, _| panic!("{} was set", name));
+        context.freeze();
+        context.scopes().push();
+        let expr = parse("gold = gold + level")?;
+        assert_eq!(block_on(eval_async(&expr, &mut context, &mut store))?, TalkValue::Int(18));
+        assert_eq!(store.saved["gold"], 18);
+        assert_eq!(context.scopes().depth(), 1);
+        Ok(())
+    }
+}
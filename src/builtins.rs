@@ -0,0 +1,259 @@
+//! Functions available to every script. A function registered on the context
+//! with the same name takes precedence.
+
+use std::cmp::Ordering;
+
+use crate::eval::{TalkEvalError, TalkEvalErrorKind, TalkValue};
+
+pub type Builtin = for<'w> fn(Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError>;
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    match name {
+        "len" => Some(len),
+        "upper" => Some(upper),
+        "lower" => Some(lower),
+        "abs" => Some(abs),
+        "min" => Some(min),
+        "max" => Some(max),
+        "has" => Some(has),
+        "bool" => Some(bool),
+        "num" => Some(num),
+        "str" => Some(str),
+        _ => None,
+    }
+}
+
+fn expect_args<'w>(name: &str, args: Vec<TalkValue<'w>>, count: usize) -> Result<Vec<TalkValue<'w>>, TalkEvalError> {
+    if args.len() != count {
+        return Err(TalkEvalError::new(&format!("{} expects {} argument(s), got {}", name, count, args.len())));
+    }
+    Ok(args)
+}
+
+fn string_arg(name: &str, args: Vec<TalkValue>) -> Result<String, TalkEvalError> {
+    match expect_args(name, args, 1)?.pop().unwrap() {
+        TalkValue::String(s) => Ok(s),
+        other => Err(other.mismatch("string")),
+    }
+}
+
+/// Characters of a `String`, elements of a `List`, or local fields of an `Object`.
+fn len<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    let arg = expect_args("len", args, 1)?.pop().unwrap();
+    let len = match &arg {
+        TalkValue::String(s) => s.chars().count(),
+        TalkValue::List(l) => l.len(),
+        TalkValue::Object(o) => o.local_len(),
+        other => return Err(other.mismatch("string, list or object")),
+    };
+    Ok(TalkValue::Int(len as i64))
+}
+
+/// Unicode-aware, so the result may be longer than the input (`"ß"` becomes `"SS"`).
+fn upper<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    Ok(TalkValue::String(string_arg("upper", args)?.to_uppercase()))
+}
+
+fn lower<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    Ok(TalkValue::String(string_arg("lower", args)?.to_lowercase()))
+}
+
+/// `abs(i64::MIN)` has no `Int` result and is an error rather than saturating.
+fn abs<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    match expect_args("abs", args, 1)?.pop().unwrap() {
+        TalkValue::Int(i) => i.checked_abs()
+            .map(TalkValue::Int)
+            .ok_or_else(|| TalkEvalErrorKind::Overflow(format!("abs({})", i)).into()),
+        TalkValue::Float(x) => Ok(TalkValue::Float(x.abs())),
+        other => Err(other.mismatch("int or float")),
+    }
+}
+
+/// The first argument that compares as `wanted` (or equal) against all the others.
+fn extreme<'w>(name: &str, args: Vec<TalkValue<'w>>, wanted: Ordering) -> Result<TalkValue<'w>, TalkEvalError> {
+    if args.len() < 2 {
+        return Err(TalkEvalError::new(&format!("{} expects at least 2 arguments, got {}", name, args.len())));
+    }
+    let mut best: Option<TalkValue> = None;
+    for arg in args {
+        if !matches!(arg, TalkValue::Int(_) | TalkValue::Float(_)) {
+            return Err(arg.mismatch("int or float"));
+        }
+        best = match best {
+            Some(b) if b.try_cmp(&arg)? != wanted.reverse() => Some(b),
+            _ => Some(arg),
+        };
+    }
+    Ok(best.unwrap())
+}
+
+fn min<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    extreme("min", args, Ordering::Less)
+}
+
+fn max<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    extreme("max", args, Ordering::Greater)
+}
+
+/// `has(object, "name")`: whether `object` has a local field `name`. Never creates it.
+fn has<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    let mut args = expect_args("has", args, 2)?;
+    let name = match args.pop().unwrap() {
+        TalkValue::String(s) => s,
+        other => return Err(other.mismatch("string")),
+    };
+    match &args[0] {
+        TalkValue::Object(o) => Ok(TalkValue::Bool(o.contains(&name))),
+        other => Err(other.mismatch("object")),
+    }
+}
+
+/// Parses `"true"` or `"false"`, case-sensitively and without trimming, so config
+/// values like `"True"` or `" true"` are errors rather than guesses. A `Bool` passes through.
+fn bool<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    match expect_args("bool", args, 1)?.pop().unwrap() {
+        TalkValue::Bool(b) => Ok(TalkValue::Bool(b)),
+        TalkValue::String(s) => match s.as_str() {
+            "true" => Ok(TalkValue::Bool(true)),
+            "false" => Ok(TalkValue::Bool(false)),
+            _ => Err(TalkEvalError::new(&format!("bool expects \"true\" or \"false\", got {:?}", s))),
+        },
+        other => Err(other.mismatch("string or bool")),
+    }
+}
+
+/// Parses a decimal `String` into an `Int`, without trimming, like `bool`. Numbers pass
+/// through, and a `Bool` becomes `0` or `1`.
+fn num<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    match expect_args("num", args, 1)?.pop().unwrap() {
+        TalkValue::String(s) => s.parse()
+            .map(TalkValue::Int)
+            .map_err(|_| TalkEvalError::new(&format!("num expects an integer, got {:?}", s))),
+        number @ TalkValue::Int(_) | number @ TalkValue::Float(_) => Ok(number),
+        TalkValue::Bool(b) => Ok(TalkValue::Int(b as i64)),
+        other => Err(other.mismatch("string, int, float or bool")),
+    }
+}
+
+/// Any value as its `Display` text: `str(null)` is `"null"`, and a `String` is unchanged.
+fn str<'w>(args: Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> {
+    let arg = expect_args("str", args, 1)?.pop().unwrap();
+    Ok(TalkValue::String(arg.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{eval_str, TalkEvalError, TalkObject, TalkObjectProxy, TalkValue};
+
+    #[test]
+    fn test_len() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        let mut inventory = TalkObject::new();
+        inventory.set("key", TalkValue::Bool(true))?;
+        inventory.set("map", TalkValue::Bool(true))?;
+        ctx.set("inventory", TalkValue::Object(Box::new(inventory)))?;
+        ctx.set("list", TalkValue::List(vec![1.into(), 2.into(), 3.into()]))?;
+
+        assert_eq!(eval_str("len(\"straße\")", &mut ctx)?, TalkValue::Int(6));
+        assert_eq!(eval_str("len(list)", &mut ctx)?, TalkValue::Int(3));
+        assert_eq!(eval_str("len(inventory)", &mut ctx)?, TalkValue::Int(2));
+
+        assert_eq!(eval_str("len(1)", &mut ctx).unwrap_err().text(), "expected string, list or object, got int");
+        assert_eq!(eval_str("len(true)", &mut ctx).unwrap_err().text(), "expected string, list or object, got bool");
+        assert_eq!(eval_str("len(list, 1)", &mut ctx).unwrap_err().text(), "len expects 1 argument(s), got 2");
+
+        // Registered functions shadow builtins.
+        ctx.register_fn("len", |_| Ok(TalkValue::Int(-1)));
+        assert_eq!(eval_str("len(list)", &mut ctx)?, TalkValue::Int(-1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_case() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("upper(\"straße\")", &mut ctx)?, TalkValue::new_str("STRASSE"));
+        assert_eq!(eval_str("lower(\"ÄBC\")", &mut ctx)?, TalkValue::new_str("äbc"));
+        assert_eq!(eval_str("upper(1)", &mut ctx).unwrap_err().text(), "expected string, got int");
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("abs(-5)", &mut ctx)?, TalkValue::Int(5));
+        assert_eq!(eval_str("abs(-2.5)", &mut ctx)?, TalkValue::Float(2.5));
+        assert_eq!(eval_str("min(3, 1, 2)", &mut ctx)?, TalkValue::Int(1));
+        assert_eq!(eval_str("max(3, 1, 2)", &mut ctx)?, TalkValue::Int(3));
+        assert_eq!(eval_str("max(0.5, 1.5)", &mut ctx)?, TalkValue::Float(1.5));
+
+        ctx.set("lowest", TalkValue::Int(i64::MIN))?;
+        assert_eq!(eval_str("abs(lowest + 1)", &mut ctx)?, TalkValue::Int(i64::MAX));
+        assert_eq!(eval_str("abs(lowest)", &mut ctx).unwrap_err().text(), "Integer overflow in abs(-9223372036854775808)");
+
+        assert_eq!(eval_str("min(\"a\", 1)", &mut ctx).unwrap_err().text(), "expected int or float, got string");
+        assert_eq!(eval_str("max(1)", &mut ctx).unwrap_err().text(), "max expects at least 2 arguments, got 1");
+        assert!(eval_str("abs(true)", &mut ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_has() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        let mut inventory = TalkObject::new();
+        inventory.set("key", TalkValue::Bool(true))?;
+        ctx.set("inventory", TalkValue::Object(Box::new(inventory)))?;
+
+        assert_eq!(eval_str("has(inventory, \"key\")", &mut ctx)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("has(inventory, \"map\")", &mut ctx)?, TalkValue::Bool(false));
+        assert_eq!(eval_str("len(inventory)", &mut ctx)?, TalkValue::Int(1));
+
+        assert_eq!(eval_str("has(1, \"key\")", &mut ctx).unwrap_err().text(), "expected object, got int");
+        assert_eq!(eval_str("has(inventory, 1)", &mut ctx).unwrap_err().text(), "expected string, got int");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bool() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("bool(\"true\")", &mut ctx)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("bool(\"false\")", &mut ctx)?, TalkValue::Bool(false));
+        assert_eq!(eval_str("bool(false)", &mut ctx)?, TalkValue::Bool(false));
+
+        assert_eq!(eval_str("bool(\"True\")", &mut ctx).unwrap_err().text(), "bool expects \"true\" or \"false\", got \"True\"");
+        assert!(eval_str("bool(\" true\")", &mut ctx).is_err());
+        assert_eq!(eval_str("bool(1)", &mut ctx).unwrap_err().text(), "expected string or bool, got int");
+        Ok(())
+    }
+
+    #[test]
+    fn test_num() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("num(\"42\")", &mut ctx)?, TalkValue::Int(42));
+        assert_eq!(eval_str("num(\"-7\") + 1", &mut ctx)?, TalkValue::Int(-6));
+        assert_eq!(eval_str("num(42)", &mut ctx)?, TalkValue::Int(42));
+        assert_eq!(eval_str("num(2.5)", &mut ctx)?, TalkValue::Float(2.5));
+        assert_eq!(eval_str("num(true) + num(false)", &mut ctx)?, TalkValue::Int(1));
+
+        assert_eq!(eval_str("num(\"abc\")", &mut ctx).unwrap_err().text(), "num expects an integer, got \"abc\"");
+        assert!(eval_str("num(\" 42\")", &mut ctx).is_err());
+        assert!(eval_str("num(\"4.2\")", &mut ctx).is_err());
+        assert!(eval_str("num(\"99999999999999999999\")", &mut ctx).is_err());
+        assert_eq!(eval_str("num(null)", &mut ctx).unwrap_err().text(), "expected string, int, float or bool, got null");
+        Ok(())
+    }
+
+    #[test]
+    fn test_str() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("str(true)", &mut ctx)?, TalkValue::new_str("true"));
+        assert_eq!(eval_str("str(42) + str(1.5)", &mut ctx)?, TalkValue::new_str("421.5"));
+        assert_eq!(eval_str("str(\"hi\")", &mut ctx)?, TalkValue::new_str("hi"));
+        assert_eq!(eval_str("str(null)", &mut ctx)?, TalkValue::new_str("null"));
+        assert_eq!(eval_str("str([1, \"a\"])", &mut ctx)?, TalkValue::new_str("[1, a]"));
+        assert_eq!(eval_str("str({ hp: 3 })", &mut ctx)?, TalkValue::new_str("{hp: 3}"));
+        assert_eq!(eval_str("num(str(12)) == 12", &mut ctx)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("str()", &mut ctx).unwrap_err().text(), "str expects 1 argument(s), got 0");
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Parsed programs keyed by their source, so a line evaluated every frame is parsed once.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::eval::{Eval, Expression, TalkEvalError, TalkObject, TalkValue};
+use crate::parser::{parse_spanned_with_limit, MAX_PARSE_DEPTH};
+
+/// How many programs a `ProgramCache` keeps, unless configured otherwise.
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// Holds up to `capacity` parsed programs and evicts the least recently used one to make
+/// room for another. Sources that fail to parse are not cached.
+///
+/// Eviction scans the whole cache, which is cheap at the sizes a game's dialogue needs.
+#[derive(Debug)]
+pub struct ProgramCache {
+    capacity: usize,
+    /// Incremented on every lookup; an entry's `last_used` is the tick it was last returned at.
+    tick: u64,
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    program: Rc<Expression>,
+    last_used: u64,
+}
+
+impl Default for ProgramCache {
+    fn default() -> Self { Self::new(DEFAULT_CACHE_CAPACITY) }
+}
+
+impl ProgramCache {
+    /// A cache of capacity 0 parses every time.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, tick: 0, entries: HashMap::new() }
+    }
+
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn contains(&self, src: &str) -> bool { self.entries.contains_key(src) }
+
+    pub fn clear(&mut self) { self.entries.clear() }
+
+    /// Returns the cached program for `src`, parsing and caching it on a miss. Programs are
+    /// parsed with `parse_spanned`, so their evaluation errors point into `src`.
+    pub fn get_or_parse(&mut self, src: &str) -> Result<Rc<Expression>, TalkEvalError> {
+        self.get_or_parse_with_limit(src, MAX_PARSE_DEPTH)
+    }
+
+    fn get_or_parse_with_limit(&mut self, src: &str, max_depth: usize) -> Result<Rc<Expression>, TalkEvalError> {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(src) {
+            entry.last_used = self.tick;
+            return Ok(entry.program.clone());
+        }
+
+        let program = Rc::new(parse_spanned_with_limit(src, max_depth)?);
+        if self.capacity == 0 {
+            return Ok(program);
+        }
+        if self.entries.len() == self.capacity {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(src, _)| src.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(src.to_string(), Entry { program: program.clone(), last_used: self.tick });
+        Ok(program)
+    }
+
+    /// Like `eval::eval_str`, but parses through the cache.
+    pub fn eval_str<'world>(&mut self, src: &str, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+        let program = self.get_or_parse_with_limit(src, context.max_parse_depth())?;
+        program.eval(context).map(Cow::into_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::cache::ProgramCache;
+    use crate::eval::{TalkEvalError, TalkObject, TalkValue};
+
+    #[test]
+    fn test_cache_hit() -> Result<(), TalkEvalError> {
+        let mut cache = ProgramCache::new(4);
+        let first = cache.get_or_parse("hp > 0 ? \"alive\" : \"dead\"")?;
+        let second = cache.get_or_parse("hp > 0 ? \"alive\" : \"dead\"")?;
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        assert!(cache.get_or_parse("hp >").is_err());
+        assert_eq!(cache.len(), 1);
+
+        let mut context = TalkObject::new();
+        assert_eq!(cache.eval_str("x = 2", &mut context)?, TalkValue::Int(2));
+        assert_eq!(cache.eval_str("x * 2", &mut context)?, TalkValue::Int(4));
+        assert_eq!(cache.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_eviction() -> Result<(), TalkEvalError> {
+        let mut cache = ProgramCache::new(2);
+        let a = cache.get_or_parse("a")?;
+        cache.get_or_parse("b")?;
+        // Touching `a` makes `b` the least recently used.
+        cache.get_or_parse("a")?;
+        cache.get_or_parse("c")?;
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains("a") && cache.contains("c"));
+        assert!(!cache.contains("b"));
+        assert!(Rc::ptr_eq(&a, &cache.get_or_parse("a")?));
+
+        let mut uncached = ProgramCache::new(0);
+        let first = uncached.get_or_parse("a")?;
+        assert!(!Rc::ptr_eq(&first, &uncached.get_or_parse("a")?));
+        assert!(uncached.is_empty());
+        Ok(())
+    }
+}
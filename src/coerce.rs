@@ -0,0 +1,192 @@
+//! The conversions operators apply to their operands. Keeping them here means
+//! arithmetic, concatenation and conditions agree on what a value converts to.
+//!
+//! | value    | `to_number` | `to_string`            | `to_bool`                 |
+//! |----------|-------------|------------------------|---------------------------|
+//! | `Int`    | itself      | decimal, `42`          | non-zero                  |
+//! | `Float`  | error       | shortest form, `1.5`   | non-zero and not NaN      |
+//! | `String` | error       | itself                 | non-empty                 |
+//! | `Bool`   | error       | `true` / `false`       | itself                    |
+//! | `Null`   | error       | `null`                 | `false`                   |
+//! | `List`   | error       | `[a, b]`               | non-empty                 |
+//! | `Object` | error       | `{k: v}`, keys sorted  | has fields                |
+//!
+//! Comparisons only coerce between `Int` and `Float`, see `compare_numbers`. Otherwise
+//! `==` is `false` across types, unless the context opts into `loose_eq`, and ordering
+//! needs values of the same type, see `TalkValue::try_cmp`.
+
+use std::cmp::Ordering;
+
+use crate::eval::{TalkEvalError, TalkValue};
+
+/// The integer operand of arithmetic. Only `Int` converts.
+pub fn to_number(val: &TalkValue) -> Result<i64, TalkEvalError> {
+    val.as_int().ok_or_else(|| val.mismatch("int"))
+}
+
+/// The text a value contributes to a concatenation, same as its `Display`.
+pub fn to_string(val: &TalkValue) -> String {
+    val.to_string()
+}
+
+/// Truthiness, as used by conditions and `&&`, `||`, `xor` and `!`.
+pub fn to_bool(val: &TalkValue) -> bool {
+    val.as_bool()
+}
+
+/// Orders two numbers, `Int` or `Float` in any combination; `None` for anything else or NaN.
+///
+/// An `Int` is compared to a `Float` exactly rather than by converting it to `f64`, which
+/// would round integers beyond 2^53: `9_007_199_254_740_993` is greater than the float
+/// `9_007_199_254_740_992.0`, although it converts to that very float.
+pub fn compare_numbers(l: &TalkValue, r: &TalkValue) -> Option<Ordering> {
+    match (l, r) {
+        (TalkValue::Int(a), TalkValue::Int(b)) => Some(a.cmp(b)),
+        (TalkValue::Float(a), TalkValue::Float(b)) => a.partial_cmp(b),
+        (TalkValue::Int(a), TalkValue::Float(b)) => compare_int_float(*a, *b),
+        (TalkValue::Float(a), TalkValue::Int(b)) => compare_int_float(*b, *a).map(Ordering::reverse),
+        _ => None,
+    }
+}
+
+/// Equality for contexts built with `TalkObjectBuilder::loose_equality`:
+///
+/// - numbers compare by value, as with `compare_numbers`;
+/// - a `String` equals a number if it parses as one, ignoring surrounding whitespace:
+///   `"1" == 1`, `" 1.5 " == 1.5`;
+/// - a `String` equals a `Bool` if it reads `"true"` or `"false"` accordingly;
+/// - anything else only equals a value of its own type, and `Null` only equals `Null`.
+pub fn loose_eq<'world>(l: &TalkValue<'world>, r: &TalkValue<'world>) -> bool {
+    use TalkValue::{Bool, Float, Int, String};
+    if let Some(o) = compare_numbers(l, r) {
+        return o == Ordering::Equal;
+    }
+    match (l, r) {
+        (String(s), n @ Int(_)) | (String(s), n @ Float(_)) | (n @ Int(_), String(s)) | (n @ Float(_), String(s)) => {
+            parse_number(s).is_some_and(|parsed| compare_numbers(&parsed, n) == Some(Ordering::Equal))
+        }
+        (String(s), Bool(b)) | (Bool(b), String(s)) => s == &b.to_string(),
+        _ => l == r,
+    }
+}
+
+fn parse_number(s: &str) -> Option<TalkValue<'static>> {
+    let s = s.trim();
+    s.parse().map(TalkValue::Int).ok().or_else(|| s.parse().map(TalkValue::Float).ok())
+}
+
+fn compare_int_float(i: i64, x: f64) -> Option<Ordering> {
+    // -2^63 and 2^63 are exact in f64, unlike i64::MAX.
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if x.is_nan() {
+        return None;
+    }
+    if x >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if x < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    let whole = x.trunc();
+    match i.cmp(&(whole as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(x - whole)),
+        unequal => Some(unequal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::coerce::{compare_numbers, loose_eq, to_bool, to_number, to_string};
+    use crate::eval::{TalkObject, TalkObjectProxy, TalkValue};
+
+    fn samples() -> Vec<TalkValue<'static>> {
+        let mut obj = TalkObject::new();
+        obj.set("b", TalkValue::new_int(2)).unwrap();
+        obj.set("a", TalkValue::new_str("x")).unwrap();
+        vec![
+            TalkValue::new_int(-3),
+            TalkValue::new_float(1.5),
+            TalkValue::new_str("7"),
+            TalkValue::Bool(true),
+            TalkValue::Null,
+            TalkValue::List(vec![TalkValue::new_int(1), TalkValue::new_str("a")]),
+            TalkValue::Object(Box::new(obj)),
+        ]
+    }
+
+    #[test]
+    fn test_to_number() {
+        let results: Vec<_> = samples().iter().map(|v| to_number(v).map_err(|e| e.text())).collect();
+        assert_eq!(results, vec![
+            Ok(-3),
+            Err("expected int, got float".to_string()),
+            Err("expected int, got string".to_string()),
+            Err("expected int, got bool".to_string()),
+            Err("expected int, got null".to_string()),
+            Err("expected int, got list".to_string()),
+            Err("expected int, got object".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_to_string() {
+        let results: Vec<_> = samples().iter().map(to_string).collect();
+        assert_eq!(results, vec!["-3", "1.5", "7", "true", "null", "[1, a]", "{a: x, b: 2}"]);
+        assert_eq!(to_string(&TalkValue::new_float(2.0)), "2");
+    }
+
+    #[test]
+    fn test_to_bool() {
+        let results: Vec<_> = samples().iter().map(to_bool).collect();
+        assert_eq!(results, vec![true, true, true, true, false, true, true]);
+
+        let falsy = [
+            TalkValue::new_int(0),
+            TalkValue::new_float(f64::NAN),
+            TalkValue::new_str(""),
+            TalkValue::Bool(false),
+            TalkValue::new_list(),
+            TalkValue::new_obj(),
+        ];
+        assert!(falsy.iter().all(|v| !to_bool(v)));
+    }
+
+    #[test]
+    fn test_compare_numbers() {
+        let cmp = |l: TalkValue, r: TalkValue| compare_numbers(&l, &r);
+        assert_eq!(cmp(TalkValue::new_int(1), TalkValue::new_float(1.5)), Some(Ordering::Less));
+        assert_eq!(cmp(TalkValue::new_float(1.5), TalkValue::new_int(1)), Some(Ordering::Greater));
+        assert_eq!(cmp(TalkValue::new_int(-2), TalkValue::new_float(-1.5)), Some(Ordering::Less));
+        assert_eq!(cmp(TalkValue::new_int(1), TalkValue::new_float(1.0)), Some(Ordering::Equal));
+        assert_eq!(cmp(TalkValue::new_int(0), TalkValue::new_float(-0.0)), Some(Ordering::Equal));
+
+        // Beyond 2^53, where `as f64` would round the integer.
+        let big = 9_007_199_254_740_993;
+        assert_eq!(big as f64, 9_007_199_254_740_992.0);
+        assert_eq!(cmp(TalkValue::new_int(big), TalkValue::new_float(big as f64)), Some(Ordering::Greater));
+        assert_eq!(cmp(TalkValue::new_int(big - 1), TalkValue::new_float(big as f64)), Some(Ordering::Equal));
+
+        assert_eq!(cmp(TalkValue::new_int(i64::MAX), TalkValue::new_float(i64::MAX as f64)), Some(Ordering::Less));
+        assert_eq!(cmp(TalkValue::new_int(i64::MIN), TalkValue::new_float(i64::MIN as f64)), Some(Ordering::Equal));
+        assert_eq!(cmp(TalkValue::new_int(i64::MIN), TalkValue::new_float(f64::NEG_INFINITY)), Some(Ordering::Greater));
+        assert_eq!(cmp(TalkValue::new_int(1), TalkValue::new_float(f64::NAN)), None);
+        assert_eq!(cmp(TalkValue::new_int(1), TalkValue::new_str("1")), None);
+    }
+
+    #[test]
+    fn test_loose_eq() {
+        let eq = |l: TalkValue<'static>, r: TalkValue<'static>| loose_eq(&l, &r);
+        assert!(eq(TalkValue::new_int(1), TalkValue::new_str("1")));
+        assert!(eq(TalkValue::new_str("2.0"), TalkValue::new_int(2)));
+        assert!(eq(TalkValue::new_float(0.5), TalkValue::new_str("0.5")));
+        assert!(!eq(TalkValue::new_int(1), TalkValue::new_str("1x")));
+        assert!(!eq(TalkValue::new_float(f64::NAN), TalkValue::new_str("NaN")));
+        assert!(eq(TalkValue::Bool(false), TalkValue::new_str("false")));
+        assert!(!eq(TalkValue::Bool(true), TalkValue::new_int(1)));
+        assert!(eq(TalkValue::Null, TalkValue::Null));
+        assert!(!eq(TalkValue::Null, TalkValue::new_str("")));
+        assert!(eq(TalkValue::new_str("a"), TalkValue::new_str("a")));
+    }
+}
@@ -1,208 +1,4160 @@
-use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
-use std::ops::{DerefMut};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{hash_map, HashMap};
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{DerefMut, Index, IndexMut};
+use std::sync::Arc;
 
 use serde::{Serialize, Deserialize};
 
+use crate::builtins;
+use crate::coerce;
+use crate::intern::{self, Key};
+use crate::parser::{parse_spanned_with_limit, MAX_PARSE_DEPTH};
+
 /// Here 'world is the root object for all the in-game world.
-#[derive(Serialize, Deserialize)]
-#[serde(untagged)]
+///
+/// Cloning is deep; objects are cloned without their proxies, see `TalkObject::clone`.
+///
+/// Deserialization is untagged, so variants are tried in declaration order and the first
+/// that accepts the input wins. JSON keeps numbers, strings and booleans apart, so each
+/// variant survives a round trip; the exception is a non-finite `Float`, which
+/// `serde_json` writes as `null`.
+///
+/// With the `tagged-serde` feature, values are adjacently tagged instead, as in
+/// `{"type": "int", "value": 42}` or `{"type": "null"}`, which round-trips every variant
+/// without relying on declaration order.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "tagged-serde"), serde(untagged))]
+#[cfg_attr(feature = "tagged-serde", serde(tag = "type", content = "value", rename_all = "lowercase"))]
 pub enum TalkValue<'world> {
     Int(i64),
+    // Must stay after `Int`: untagged deserialization tries variants in order,
+    // and integers should not be widened into floats.
+    Float(f64),
     String(String),
     Bool(bool),
+    /// An absent value, as opposed to an empty `Object`.
+    Null,
+    List(Vec<TalkValue<'world>>),
     Object(Box<TalkObject<'world>>)
 }
 
+/// Tags every variant and quotes strings, so `String("42")` and `Int(42)` differ in logs.
+/// `{:#?}` spreads lists and objects over indented lines.
 impl<'world> Debug for TalkValue<'world> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::List(l) if f.alternate() => write!(f, "List({:#?})", l),
+            Self::Object(o) if f.alternate() => write!(f, "Object({:#?})", o),
+            Self::Int(i) => write!(f, "Int({})", i),
+            Self::Float(x) => write!(f, "Float({:?})", x),
+            Self::String(s) => write!(f, "String({:?})", s),
+            Self::Bool(b) => write!(f, "Bool({})", b),
+            Self::Null => write!(f, "Null"),
+            Self::List(l) => write!(f, "List({:?})", l),
+            Self::Object(o) => write!(f, "Object({:?})", o),
+        }
+    }
+}
+
+/// Plain user-facing rendering, as a script would print it.
+impl<'world> Display for TalkValue<'world> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int(i) => write!(f, "{}", i),
+            Self::Float(x) => write!(f, "{}", x),
             Self::String(s) => write!(f, "{}", s),
             Self::Bool(b) => write!(f, "{}", b),
-            Self::Object(o) => write!(f, "{:?}", o),
+            Self::Null => write!(f, "null"),
+            Self::List(l) => {
+                write!(f, "[")?;
+                for (i, v) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(o) => write!(f, "{}", o),
+        }
+    }
+}
+
+/// Values of different variants are never equal; `Int(1) != Float(1.0)`.
+impl<'world> PartialEq for TalkValue<'world> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Null, Self::Null) => true,
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Object(a), Self::Object(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Only values of the same scalar variant are ordered; everything else,
+/// including any `List` or `Object`, yields `None`.
+impl<'world> PartialOrd for TalkValue<'world> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.partial_cmp(b),
+            (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
+            (Self::String(a), Self::String(b)) => a.partial_cmp(b),
+            (Self::Bool(a), Self::Bool(b)) => a.partial_cmp(b),
+            _ => None,
         }
     }
 }
 
+impl<'world> From<i64> for TalkValue<'world> {
+    fn from(i: i64) -> Self { TalkValue::new_int(i) }
+}
+
+impl<'world> From<f64> for TalkValue<'world> {
+    fn from(f: f64) -> Self { TalkValue::new_float(f) }
+}
+
+impl<'world> From<bool> for TalkValue<'world> {
+    fn from(b: bool) -> Self { TalkValue::Bool(b) }
+}
+
+impl<'world> From<&str> for TalkValue<'world> {
+    fn from(s: &str) -> Self { TalkValue::new_str(s) }
+}
+
+impl<'world> From<String> for TalkValue<'world> {
+    fn from(s: String) -> Self { TalkValue::String(s) }
+}
+
 impl<'world> TalkValue<'world> {
     #[allow(dead_code)] pub fn new_obj() -> Self { TalkValue::Object(Box::new(TalkObject::new())) }
     #[allow(dead_code)] pub fn new_str(s: &str) -> Self { TalkValue::String(s.to_string()) }
     #[allow(dead_code)] pub fn new_int(i: i64) -> Self { TalkValue::Int(i) }
+    #[allow(dead_code)] pub fn new_float(f: f64) -> Self { TalkValue::Float(f) }
+    #[allow(dead_code)] pub fn new_list() -> Self { TalkValue::List(Vec::new()) }
+
+    #[allow(dead_code)] pub fn is_null(&self) -> bool { matches!(self, TalkValue::Null) }
 
     #[allow(dead_code)]
     pub fn as_bool(&self) -> bool {
         match self {
             TalkValue::Int(i) => *i != 0,
+            // NaN is falsy, as is -0.0 (it compares equal to 0.0).
+            TalkValue::Float(x) => *x != 0.0 && !x.is_nan(),
             TalkValue::String(s) => !s.is_empty(),
             TalkValue::Bool(b) => *b,
+            TalkValue::Null => false,
+            TalkValue::List(l) => !l.is_empty(),
             TalkValue::Object(o) => !o.is_empty()
         }
     }
 
+    /// Strict accessors: `Some` only for the matching variant, with no coercion.
+    #[allow(dead_code)]
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            TalkValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TalkValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn as_bool_opt(&self) -> Option<bool> {
+        match self {
+            TalkValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn as_object(&mut self) -> Result<&mut TalkObject<'world>, TalkEvalError> {
         match self {
-            TalkValue::Int(_) => Err(TalkEvalError::new("Object expected, got Int")),
-            TalkValue::String(_) => Err(TalkEvalError::new("Object expected, got String")),
-            TalkValue::Bool(_) => Err(TalkEvalError::new("Object expected, got Bool")),
             TalkValue::Object(o) => Ok(o.deref_mut()),
+            // A List is never treated as an Object, even though both are containers.
+            other => Err(other.mismatch("object")),
+        }
+    }
+
+    /// Like `clone`, but with every object's proxies dropped, so the copy borrows nothing
+    /// from the world; see `TalkObject::deep_clone`.
+    pub fn deep_clone(&self) -> TalkValue<'static> { self.detached() }
+
+    /// `deep_clone` for any lifetime, which is how values of a `'static` base are copied
+    /// into an object of a shorter `'world`.
+    fn detached<'a>(&self) -> TalkValue<'a> {
+        match self {
+            TalkValue::Int(i) => TalkValue::Int(*i),
+            TalkValue::Float(x) => TalkValue::Float(*x),
+            TalkValue::String(s) => TalkValue::String(s.clone()),
+            TalkValue::Bool(b) => TalkValue::Bool(*b),
+            TalkValue::Null => TalkValue::Null,
+            TalkValue::List(l) => TalkValue::List(l.iter().map(TalkValue::detached).collect()),
+            TalkValue::Object(o) => TalkValue::Object(Box::new(o.detached())),
+        }
+    }
+
+    /// The name errors use for this value's type, such as `"int"` in "expected string, got int".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TalkValue::Int(_) => "int",
+            TalkValue::Float(_) => "float",
+            TalkValue::String(_) => "string",
+            TalkValue::Bool(_) => "bool",
+            TalkValue::Null => "null",
+            TalkValue::List(_) => "list",
+            TalkValue::Object(_) => "object",
+        }
+    }
+
+    pub(crate) fn mismatch(&self, expected: &'static str) -> TalkEvalError {
+        TalkEvalErrorKind::TypeMismatch { expected, got: self.type_name() }.into()
+    }
+
+    /// Like `partial_cmp`, but also orders an `Int` against a `Float` (see
+    /// `coerce::compare_numbers`), and reports incomparable values as an error.
+    #[allow(dead_code)]
+    pub fn try_cmp(&self, other: &Self) -> Result<Ordering, TalkEvalError> {
+        coerce::compare_numbers(self, other).or_else(|| self.partial_cmp(other)).ok_or_else(|| TalkEvalError::new(
+            &format!("cannot order {} against {}", self.type_name(), other.type_name())))
+    }
+
+    /// Returns `None` for non-lists as well as for out-of-bounds indices.
+    #[allow(dead_code)]
+    pub fn get_index(&self, i: usize) -> Option<&TalkValue<'world>> {
+        match self {
+            TalkValue::List(l) => l.get(i),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_index(&mut self, i: usize, v: TalkValue<'world>) -> Result<(), TalkEvalError> {
+        match self {
+            TalkValue::List(l) => match l.get_mut(i) {
+                Some(slot) => {
+                    *slot = v;
+                    Ok(())
+                }
+                None => Err(TalkEvalErrorKind::IndexOutOfBounds(i).into()),
+            },
+            other => Err(other.mismatch("list")),
         }
     }
 
+    /// Floats are truncated towards zero, saturating at the `i64` bounds.
     #[allow(dead_code)]
     pub fn into_int(self) -> Result<i64, TalkEvalError> {
         match self {
             TalkValue::Int(i) => Ok(i),
-            TalkValue::String(s) => Err(TalkEvalError::new(&format!("Integer value expected, got String {}", s))),
-            TalkValue::Bool(b) => Err(TalkEvalError::new(&format!("Integer value expected, got Bool {}", b))),
-            TalkValue::Object(_) => Err(TalkEvalError::new(&"Integer value expected, got Object".to_string())),
+            TalkValue::Float(x) if x.is_finite() => Ok(x as i64),
+            other => Err(other.mismatch("int")),
+        }
+    }
+
+    /// Integers are promoted to `f64`, which is lossy above 2^53.
+    #[allow(dead_code)]
+    pub fn into_float(self) -> Result<f64, TalkEvalError> {
+        match self {
+            TalkValue::Int(i) => Ok(i as f64),
+            TalkValue::Float(x) => Ok(x),
+            other => Err(other.mismatch("float")),
         }
     }
 }
 
+pub const DEFAULT_MAX_ITERATIONS: usize = 10_000;
+
+/// How many field accesses a single path like `a.b.c` may chain, unless configured otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// How deeply expressions may nest during evaluation, unless configured otherwise.
+pub const DEFAULT_MAX_EVAL_DEPTH: usize = 128;
+
+/// How deep `TalkObject::resolve` follows nested objects before giving up.
+pub const MAX_RESOLVE_DEPTH: usize = 64;
+
+/// A host function callable from scripts.
+///
+/// It works for any `'world` and can't borrow from the world: a closure tied to `'world`
+/// would force every proxy to strictly outlive its `TalkObject`. Share host state through
+/// owned handles such as `Rc<RefCell<_>>` instead, or `Arc<Mutex<_>>` with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub type TalkFn = Box<dyn for<'w> FnMut(Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError>>;
+
+#[cfg(feature = "sync")]
+pub type TalkFn = Box<dyn for<'w> FnMut(Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> + Send + Sync>;
+
+/// A callback registered with `TalkObject::on_set`, given each field name and value stored.
+/// Like `TalkFn`, it works for any `'world` and can't borrow from the world.
+#[cfg(not(feature = "sync"))]
+pub type SetObserver = Box<dyn for<'w> FnMut(&str, &TalkValue<'w>)>;
+
+#[cfg(feature = "sync")]
+pub type SetObserver = Box<dyn for<'w> FnMut(&str, &TalkValue<'w>) + Send + Sync>;
+
+/// `Send + Sync` with the `sync` feature, and no bound at all without it. Proxies and host
+/// functions must implement it, which with the feature makes `TalkObject` `Send + Sync`, so
+/// a context can be handed to another thread together with the proxies it borrows.
+#[cfg(feature = "sync")]
+pub trait SyncBound: Send + Sync {}
+
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> SyncBound for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait SyncBound {}
+
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> SyncBound for T {}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct TalkObject<'world> {
     // fn get(&self, name: &str) -> Option<&TalkValue>;
     // fn set(&mut self, name: &str, val: TalkValue);
     #[serde(flatten)]
-    map: HashMap<String, TalkValue<'world>>,
+    map: HashMap<Key, TalkValue<'world>>,
+
+    /// External stores, in priority order: the first one resolving a name wins.
+    #[serde(skip)]
+    proxies: Vec<&'world mut dyn TalkObjectProxy<'world>>,
+
+    /// Enclosing scope, consulted by reads that miss both the proxies and the local map.
+    #[serde(skip)]
+    parent: Option<&'world mut TalkObject<'world>>,
+
+    /// Whether `get` creates missing fields, see `TalkObjectBuilder::auto_vivify`.
+    #[serde(skip)]
+    auto_vivify: bool,
+
+    /// Whether `==` and `!=` coerce across types, see `TalkObjectBuilder::loose_equality`.
+    #[serde(skip)]
+    loose_equality: bool,
+
+    /// Per-loop iteration cap, `DEFAULT_MAX_ITERATIONS` if unset.
+    #[serde(skip)]
+    max_iterations: Option<usize>,
+
+    /// Field path length cap, `DEFAULT_MAX_DEPTH` if unset.
+    #[serde(skip)]
+    max_depth: Option<usize>,
+
+    /// Expression nesting cap, `DEFAULT_MAX_EVAL_DEPTH` if unset.
+    #[serde(skip)]
+    max_eval_depth: Option<usize>,
+
+    /// Evaluation steps left, unlimited if unset; see `TalkObject::set_step_budget`.
+    #[serde(skip)]
+    step_budget: Option<u64>,
+
+    /// Whether evaluation records warnings, see `TalkObjectBuilder::collect_warnings`.
+    #[serde(skip)]
+    collect_warnings: bool,
+
+    /// Warnings recorded since the last `TalkObject::take_warnings`.
+    #[serde(skip)]
+    warnings: Vec<TalkEvalWarning>,
+
+    /// Host functions available to `Expression::Call`.
+    #[serde(skip)]
+    functions: HashMap<String, TalkFn>,
+
+    /// Block-local variables, such as loop variables.
+    #[serde(skip)]
+    scopes: ScopeStack<'world>,
+
+    /// Callbacks run after each successful `set`, see `TalkObject::on_set`.
+    #[serde(skip)]
+    observers: Vec<SetObserver>,
+
+    /// Whether `set` and `remove` are refused, see `TalkObject::freeze`.
+    #[serde(skip)]
+    frozen: bool,
 
-    // Now try to add a reference to external object...
+    /// Shared template that reads fall through to, see `TalkObject::set_base`.
     #[serde(skip)]
-    proxy: Option<&'world mut dyn TalkObjectProxy<'world>>,
+    base: Option<Arc<TalkObject<'static>>>,
+}
+
+/// Frames of block-local variables on top of a `TalkObject`. Reads resolve names from the
+/// innermost frame outward before trying the object's own fields. Every block pushes a
+/// frame on entry and pops it on exit, so its locals end with it.
+///
+/// `let x = ...` binds `x` in the innermost frame, as `for` does its variable; `let` is
+/// reserved, so it can't name a field or variable in source. Assigning to a name updates
+/// the innermost frame that binds it; names bound by no frame are assigned on the object
+/// itself, so a plain assignment never creates a local.
+#[derive(Default)]
+pub struct ScopeStack<'world> {
+    frames: Vec<HashMap<String, TalkValue<'world>>>,
+}
+
+impl<'world> ScopeStack<'world> {
+    pub fn push(&mut self) { self.frames.push(HashMap::new()) }
+
+    /// Removes the innermost frame, handing back its bindings.
+    pub fn pop(&mut self) -> Option<HashMap<String, TalkValue<'world>>> { self.frames.pop() }
+
+    pub fn depth(&self) -> usize { self.frames.len() }
+
+    /// Binds `name` in the innermost frame, shadowing outer bindings.
+    ///
+    /// # Panics
+    ///
+    /// If there is no frame.
+    pub fn bind(&mut self, name: &str, val: TalkValue<'world>) {
+        self.frames.last_mut().expect("bind() without a frame").insert(name.to_string(), val);
+    }
+
+    pub fn contains(&self, name: &str) -> bool { self.frames.iter().any(|f| f.contains_key(name)) }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
+        self.frames.iter_mut().rev().find_map(|f| f.get_mut(name))
+    }
 }
 
+/// Prints the local `map` and whether there is a proxy. `{:?}` fits on one line; `{:#?}`
+/// sorts the keys and puts each on its own line, with nested values indented.
 impl<'world> Debug for TalkObject<'world> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}, proxy: {}", self.map, self.proxy.is_some())
+        if f.alternate() {
+            let mut keys: Vec<&Key> = self.map.keys().collect();
+            keys.sort();
+            f.debug_map().entries(keys.into_iter().map(|k| (k, &self.map[k]))).finish()?;
+        } else {
+            write!(f, "{:?}", self.map)?;
+        }
+        write!(f, ", proxy: {}", !self.proxies.is_empty())
+    }
+}
+
+/// Prints the local `map` with keys sorted, so output is stable.
+impl<'world> Display for TalkObject<'world> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut keys: Vec<&Key> = self.map.keys().collect();
+        keys.sort();
+        write!(f, "{{")?;
+        for (i, k) in keys.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", k, self.map[k])?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Deep-clones the local `map`, shares the base and keeps the settings (auto-vivification,
+/// equality, limits). The proxy is a unique borrow and can't be shared, so the clone has
+/// none; registered functions, `on_set` callbacks and block-local variables are not cloned
+/// either.
+impl<'world> Clone for TalkObject<'world> {
+    fn clone(&self) -> Self {
+        TalkObject { map: self.map.clone(), base: self.base.clone(), ..self.empty_with_settings() }
+    }
+}
+
+/// Compares the local `map` only, since proxies can't be compared.
+impl<'world> PartialEq for TalkObject<'world> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+/// `object["hp"]` reads a field of the local `map`, for host code that knows it is there.
+/// Panics if the field is missing, like `HashMap`'s `Index`; proxies are not consulted.
+impl<'world> Index<&str> for TalkObject<'world> {
+    type Output = TalkValue<'world>;
+
+    fn index(&self, name: &str) -> &TalkValue<'world> {
+        match self.map.get(name) {
+            Some(value) => value,
+            None => panic!("no field {:?} in the TalkObject map", name),
+        }
+    }
+}
+
+/// `object["hp"] = value` writes a field of the local `map`, inserting it as `Null` first if
+/// missing, so it never panics. Unlike `set`, it bypasses proxies, even ones that resolve `name`.
+impl<'world> IndexMut<&str> for TalkObject<'world> {
+    fn index_mut(&mut self, name: &str) -> &mut TalkValue<'world> {
+        if !self.map.contains_key(name) {
+            self.map.insert(intern::key(name), TalkValue::Null);
+        }
+        self.map.get_mut(name).unwrap()
     }
 }
 
+/// `for (name, value) in &object` visits the fields of the local `map`, in no particular
+/// order, as `(&Key, &TalkValue)` pairs; `intern::Key` is `String`, or `Arc<str>` with the
+/// `intern-keys` feature. Fields behind a proxy and base fields the object hasn't copied
+/// are not included; iterate `resolve()`'s result for those.
+impl<'a, 'world> IntoIterator for &'a TalkObject<'world> {
+    type Item = (&'a Key, &'a TalkValue<'world>);
+    type IntoIter = hash_map::Iter<'a, Key, TalkValue<'world>>;
+
+    fn into_iter(self) -> Self::IntoIter { self.map.iter() }
+}
+
+/// Like `&TalkObject`'s, with the values borrowed mutably. Proxied and base fields are not
+/// included.
+impl<'a, 'world> IntoIterator for &'a mut TalkObject<'world> {
+    type Item = (&'a Key, &'a mut TalkValue<'world>);
+    type IntoIter = hash_map::IterMut<'a, Key, TalkValue<'world>>;
+
+    fn into_iter(self) -> Self::IntoIter { self.map.iter_mut() }
+}
+
+/// Collects `(name, value)` pairs into a new object with default settings and no proxy.
+/// A name that appears twice keeps its last value.
+impl<'world> std::iter::FromIterator<(String, TalkValue<'world>)> for TalkObject<'world> {
+    fn from_iter<I: IntoIterator<Item = (String, TalkValue<'world>)>>(pairs: I) -> Self {
+        let mut object = TalkObject::new();
+        for (name, value) in pairs {
+            object.map.insert(intern::key(&name), value);
+        }
+        object
+    }
+}
 
 /// Implement this to use other objects to evaluate in Talk.
-pub trait TalkObjectProxy<'world> {
+pub trait TalkObjectProxy<'world>: SyncBound {
     fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>>;
-    fn set(&mut self, name: &str, val: TalkValue<'world>);
+    /// Fails if the proxy refuses the write, as `ReadOnlyProxy` does.
+    fn set(&mut self, name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError>;
+    /// Removes a field, returning its value. Proxies that can't remove fields return `None`.
+    fn remove(&mut self, _name: &str) -> Option<TalkValue<'world>> { None }
+    /// Whether `get` finds `name` without creating it. Unlike `get`, this must not change
+    /// anything, such as auto-vivifying the field; the default calls `get`, so proxies
+    /// whose `get` has side effects override it.
+    fn has(&mut self, name: &str) -> bool { self.get(name).is_some() }
     fn is_empty(&self) -> bool { false }
+    /// Names this proxy can resolve. Proxies that can't enumerate their fields return
+    /// nothing, and are then skipped by `TalkObject::resolve`.
+    fn keys(&self) -> Vec<String> { Vec::new() }
 }
 
 impl<'world> TalkObjectProxy<'world> for TalkObject<'world> {
     fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
-        match self.proxy.as_mut() {
-            Some(p) => p.get(name), // self.proxy.as_mut().unwrap()
-            None => {
-                // Hack: allow undefined fields, in order to load scripts.
-                if !self.map.contains_key(name) {
-                    self.map.insert(name.to_string(), TalkValue::new_obj());
-                }
-
-                self.map.get_mut(name)
+        let proxied = !self.proxies.is_empty();
+        if let Some(v) = self.scopes.get_mut(name) {
+            return Some(v);
+        }
+        for p in self.proxies.iter_mut() {
+            if let Some(v) = p.get(name) {
+                return Some(v);
             }
         }
+        copy_shared(&mut self.map, &self.base, name);
+        if self.map.contains_key(name) {
+            return self.map.get_mut(name);
+        }
+        if let Some(v) = self.parent.as_mut().and_then(|p| p.lookup(name)) {
+            return Some(v);
+        }
+
+        // Auto-vivification lets tooling load scripts that refer to undefined fields.
+        if proxied || !self.auto_vivify || self.frozen {
+            return None;
+        }
+        self.map.insert(intern::key(name), TalkValue::new_obj());
+        self.map.get_mut(name)
+    }
 
+    fn has(&mut self, name: &str) -> bool {
+        self.scopes.contains(name)
+            || self.proxies.iter_mut().any(|p| p.has(name))
+            || self.map.contains_key(name)
+            || shared(&self.base, name).is_some()
+            || self.parent.as_mut().is_some_and(|p| p.has(name))
     }
 
-    fn set(&mut self, name: &str, val: TalkValue<'world>) {
-        match self.proxy.as_mut() {
-            Some(p) => p.set(name, val),
+    /// Writes to the first proxy that already resolves `name`, or to the local map if none does.
+    fn set(&mut self, name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+        if self.frozen {
+            return Err(TalkEvalErrorKind::ReadOnly(name.to_string()).into());
+        }
+        // Observers see the value as passed, even if a proxy stores something else.
+        let observed = if self.observers.is_empty() { None } else { Some(val.clone()) };
+        match self.proxies.iter_mut().position(|p| p.has(name)) {
+            Some(i) => self.proxies[i].set(name, val)?,
             None => {
-                self.map.insert(name.to_string(), val);
+                // Only a new field needs its name stored.
+                if let Some(slot) = self.map.get_mut(name) {
+                    *slot = val;
+                } else {
+                    self.map.insert(intern::key(name), val);
+                }
+            }
+        }
+        if let Some(val) = observed {
+            for observer in self.observers.iter_mut() {
+                observer(name, &val);
             }
         }
+        Ok(())
+    }
+
+    /// Removes from the first proxy that gives up a value, or from the local map. A frozen
+    /// object removes nothing.
+    fn remove(&mut self, name: &str) -> Option<TalkValue<'world>> {
+        if self.frozen {
+            return None;
+        }
+        for p in self.proxies.iter_mut() {
+            if let Some(v) = p.remove(name) {
+                return Some(v);
+            }
+        }
+        self.map.remove(name)
     }
 
     fn is_empty(&self) -> bool { self.map.is_empty() }
-}
 
-impl<'world> TalkObject<'world> {
-    pub fn new() -> Self { Default::default() }
+    /// Local, base and proxied field names, sorted and without duplicates.
+    fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.map.keys().map(|k| k.to_string()).collect();
+        for base in self.bases() {
+            keys.extend(base.map.keys().map(|k| k.to_string()));
+        }
+        for p in self.proxies.iter() {
+            keys.extend(p.keys());
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
 }
 
-#[derive(Debug)]
-pub struct TalkEvalError {
-    pub text: String
+/// Configures a `TalkObject`; `TalkObject::new()` is the same as `TalkObject::builder().build()`.
+#[derive(Default)]
+pub struct TalkObjectBuilder {
+    auto_vivify: bool,
+    loose_equality: bool,
+    max_iterations: Option<usize>,
+    max_depth: Option<usize>,
+    max_eval_depth: Option<usize>,
+    step_budget: Option<u64>,
+    collect_warnings: bool,
 }
 
-impl TalkEvalError {
-    // pub fn new(text: String) -> Self { TalkEvalError{ text } }
-    pub fn new(text: &str) -> Self { TalkEvalError{ text: text.to_string() } }
-}
+impl TalkObjectBuilder {
+    /// Makes `get` insert an empty object for a missing field instead of returning `None`.
+    /// Off by default, so that reads never mutate; meant for tooling that loads scripts.
+    pub fn auto_vivify(mut self, on: bool) -> Self {
+        self.auto_vivify = on;
+        self
+    }
 
-type EvalResult<'world> = Result<&'world mut TalkValue<'world>, TalkEvalError>;
+    /// Makes `==` and `!=` coerce between strings, numbers and booleans, as described in
+    /// `coerce::loose_eq`, so that `1 == "1"`. Off by default: values of different types
+    /// are unequal, except for `Int` and `Float`, which compare by value in either mode.
+    pub fn loose_equality(mut self, on: bool) -> Self {
+        self.loose_equality = on;
+        self
+    }
 
-pub trait Eval<'world, 'ast: 'world> {
-    fn eval(&'ast self, context: &'world mut TalkObject<'world>) -> EvalResult<'world>;
-}
+    /// See `TalkObject::set_max_iterations`.
+    pub fn max_iterations(mut self, max: usize) -> Self {
+        self.max_iterations = Some(max);
+        self
+    }
 
-// ...
+    /// See `TalkObject::set_max_depth`.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
 
-#[derive(Debug, Clone)] // FromPest,
-// #[pest_ast(rule(Rule::expression))]
-pub enum Expression {
-    // Assignment {
-    //     azz: Assignment,
-    // },
-    // OrTest {
-    //     or_test: OrTest,
-    // }
-    Dummy
-}
+    /// See `TalkObject::set_max_eval_depth`.
+    pub fn max_eval_depth(mut self, max: usize) -> Self {
+        self.max_eval_depth = Some(max);
+        self
+    }
 
-impl<'world, 'ast: 'world> Eval<'world, 'ast> for Expression {
-    fn eval(&'ast self, context: &'world mut TalkObject<'world>) -> EvalResult<'world> {
-        // match self {
-        //     Expression::Assignment { azz } => azz.eval(context),
-        //     Expression::OrTest { or_test } => or_test.eval(context),
-        // }
-        context
-            .get("43")
-            .ok_or(TalkEvalError::new("oops"))
+    /// See `TalkObject::set_step_budget`.
+    pub fn step_budget(mut self, steps: u64) -> Self {
+        self.step_budget = Some(steps);
+        self
+    }
+
+    /// Makes evaluation record a `TalkEvalWarning` for suspicious but valid code, such as
+    /// reading an undefined variable, for the host to collect with `TalkObject::take_warnings`.
+    /// Off by default, so that a context evaluated every frame and never drained doesn't
+    /// grow; with it on, the host is expected to drain the warnings.
+    pub fn collect_warnings(mut self, on: bool) -> Self {
+        self.collect_warnings = on;
+        self
+    }
+
+    pub fn build<'world>(self) -> TalkObject<'world> {
+        TalkObject {
+            auto_vivify: self.auto_vivify,
+            loose_equality: self.loose_equality,
+            max_iterations: self.max_iterations,
+            max_depth: self.max_depth,
+            max_eval_depth: self.max_eval_depth,
+            step_budget: self.step_budget,
+            collect_warnings: self.collect_warnings,
+            ..Default::default()
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::eval::{Expression, TalkObject, Eval, TalkObjectProxy, TalkValue, TalkEvalError};
+/// Exposes `P` to scripts for reading only: `set` fails and leaves `P` untouched.
+/// Objects reached through it stay writable themselves.
+pub struct ReadOnlyProxy<P>(pub P);
 
-    struct DummyProxy<'world> {
-        value: TalkValue<'world>,
-        value2: TalkValue<'world>,
+impl<'world, P: TalkObjectProxy<'world>> TalkObjectProxy<'world> for ReadOnlyProxy<P> {
+    fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>> { self.0.get(name) }
+
+    fn has(&mut self, name: &str) -> bool { self.0.has(name) }
+
+    fn set(&mut self, name: &str, _val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+        Err(TalkEvalErrorKind::ReadOnly(name.to_string()).into())
     }
 
-    impl<'world> DummyProxy<'world> {
-        pub fn new() -> Self {
-            Self { value: TalkValue::Int(42), value2: TalkValue::Int(43) }
+    fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    fn keys(&self) -> Vec<String> { self.0.keys() }
+}
+
+impl<'world> TalkObject<'world> {
+    pub fn new() -> Self { Default::default() }
+
+    pub fn builder() -> TalkObjectBuilder { Default::default() }
+
+    /// Limits how many times a single loop may run before evaluation fails.
+    pub fn set_max_iterations(&mut self, max: usize) { self.max_iterations = Some(max) }
+
+    pub fn max_iterations(&self) -> usize { self.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS) }
+
+    /// Limits how many fields a script may chain in one path, so that untrusted scripts
+    /// can't walk arbitrarily deep into nested objects.
+    pub fn set_max_depth(&mut self, max: usize) { self.max_depth = Some(max) }
+
+    pub fn max_depth(&self) -> usize { self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH) }
+
+    /// Limits how deeply expressions may nest, such as `-(-(-x))`, so that evaluating a
+    /// pathological script fails instead of overflowing the native stack.
+    ///
+    /// Nesting counts across contexts: a host function that evaluates a script on another
+    /// context continues from the caller's depth.
+    ///
+    /// Once set, it also limits how deeply `eval_str` lets source nest, in place of
+    /// `parser::MAX_PARSE_DEPTH`; parsing that deep takes the native stack that constant
+    /// describes.
+    pub fn set_max_eval_depth(&mut self, max: usize) { self.max_eval_depth = Some(max) }
+
+    pub fn max_eval_depth(&self) -> usize { self.max_eval_depth.unwrap_or(DEFAULT_MAX_EVAL_DEPTH) }
+
+    /// How deeply source parsed for this context may nest, see `set_max_eval_depth`.
+    pub(crate) fn max_parse_depth(&self) -> usize { self.max_eval_depth.unwrap_or(MAX_PARSE_DEPTH) }
+
+    /// Caps the total work evaluation may do: every evaluated node and every loop iteration
+    /// takes a step, and evaluation fails once the budget is spent. The budget is shared by
+    /// all evaluations on this context until it is set again, so a game can grant each
+    /// frame a fixed amount of script work. `None` lifts the cap.
+    pub fn set_step_budget(&mut self, steps: Option<u64>) { self.step_budget = steps }
+
+    /// Steps left, or `None` if unlimited.
+    pub fn step_budget(&self) -> Option<u64> { self.step_budget }
+
+    /// The warnings recorded since the last call, oldest first; see
+    /// `TalkObjectBuilder::collect_warnings`.
+    pub fn take_warnings(&mut self) -> Vec<TalkEvalWarning> { std::mem::take(&mut self.warnings) }
+
+    /// Records a warning, spanned like an error raised at this point would be.
+    fn warn(&mut self, kind: TalkEvalWarningKind) {
+        if self.collect_warnings {
+            let span = EVAL_SPAN.with(Cell::get);
+            self.warnings.push(TalkEvalWarning { kind, span });
         }
     }
 
-    impl<'world> TalkObjectProxy<'world> for DummyProxy<'world> {
-        fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
-            println!("get()");
-            if name == "42" {
-                Some(&mut self.value)
-            } else {
-                Some(&mut self.value2)
+    fn take_step(&mut self) -> Result<(), TalkEvalError> {
+        match &mut self.step_budget {
+            Some(0) => Err(TalkEvalError::new("Step budget exhausted")),
+            Some(steps) => {
+                *steps -= 1;
+                Ok(())
             }
+            None => Ok(()),
         }
+    }
 
-        fn set(&mut self, _name: &str, val: TalkValue<'world>) {
-            println!("set()");
-            self.value = val;
+    /// Makes `f` callable from scripts as `name(...)`, replacing any function of that name.
+    pub fn register_fn<F>(&mut self, name: &str, f: F)
+        where F: for<'w> FnMut(Vec<TalkValue<'w>>) -> Result<TalkValue<'w>, TalkEvalError> + SyncBound + 'static {
+        self.functions.insert(name.to_string(), Box::new(f));
+    }
+
+    /// Runs `f` with the name and value after every successful `set` on this object, which
+    /// includes script assignments and sets a proxy handles. Callbacks run in registration
+    /// order and can't be removed. They don't run for block-local variables, for writes
+    /// that bypass `set` (such as `IndexMut` or `get` followed by a write), or for nested
+    /// objects: `player.hp = 1` is a `set` on `player`, observed by `player`'s callbacks.
+    ///
+    /// A callback owns everything it captures and is never handed the object, so it can't
+    /// set fields on the object it observes: a chain of callbacks setting fields can't
+    /// recurse. To react with more sets, record the change, for example in a shared queue,
+    /// and apply it once `set` returns. A callback must not lock a mutex that the code
+    /// calling `set` holds, which would deadlock like any other re-entrant lock.
+    pub fn on_set<F>(&mut self, f: F)
+        where F: for<'w> FnMut(&str, &TalkValue<'w>) + SyncBound + 'static {
+        self.observers.push(Box::new(f));
+    }
+
+    /// Makes `set` fail with `ReadOnly` and `remove` do nothing, for this object and through
+    /// its proxies, and stops `get` from auto-vivifying. Scripts write only through `set`, so
+    /// they can't change a frozen object's fields; nested objects stay writable unless
+    /// frozen too, see `freeze_deep`. Host code holding a `&mut TalkValue`, from `get_path`
+    /// or `IndexMut` for example, can still write through it.
+    ///
+    /// There is no `unfreeze`. Clones aren't frozen, so a frozen template can be cloned into
+    /// a writable instance.
+    pub fn freeze(&mut self) { self.frozen = true }
+
+    /// Like `freeze`, and also freezes every object nested in the local `map`, in lists too.
+    pub fn freeze_deep(&mut self) {
+        fn freeze_value(value: &mut TalkValue) {
+            match value {
+                TalkValue::Object(o) => o.freeze_deep(),
+                TalkValue::List(items) => items.iter_mut().for_each(freeze_value),
+                _ => {}
+            }
         }
+        self.freeze();
+        self.map.values_mut().for_each(freeze_value);
     }
 
-    #[test]
-    fn test_bool() -> Result<(), TalkEvalError> {
-        let mut context = TalkObject::new();
-        let expr = Expression::Dummy;
+    pub fn is_frozen(&self) -> bool { self.frozen }
 
-        let mut dummy = DummyProxy::new();
-        context.proxy = Some(&mut dummy);
+    /// Whether a local field exists. Unlike `get`, never inserts it, even with auto-vivification;
+    /// proxied fields are not consulted.
+    pub fn contains(&self, name: &str) -> bool { self.map.contains_key(name) }
 
-        let a = expr.eval(&mut context)?;
-        println!("{:?}", a);
+    /// Block-local variables, see `ScopeStack`.
+    pub fn scopes(&mut self) -> &mut ScopeStack<'world> { &mut self.scopes }
+
+    /// Adds a proxy consulted after the existing ones.
+    pub fn push_proxy(&mut self, proxy: &'world mut dyn TalkObjectProxy<'world>) { self.proxies.push(proxy) }
+
+    /// Adds a proxy at `index` in the priority order, 0 being consulted first.
+    pub fn insert_proxy(&mut self, index: usize, proxy: &'world mut dyn TalkObjectProxy<'world>) {
+        self.proxies.insert(index, proxy)
+    }
+
+    /// Makes reads that miss this object fall through to `parent`, forming a scope chain.
+    /// Writes stay local: `set` and assignments never reach the parent, so a name assigned
+    /// here shadows the parent's one instead of updating it.
+    /// `parent` stays borrowed for as long as this object's `'world`.
+    pub fn set_parent(&mut self, parent: &'world mut TalkObject<'world>) { self.parent = Some(parent) }
+
+    /// Makes reads that miss the proxies and the local map fall through to `base`, ahead of
+    /// the parent, so that many objects can start from one template without each holding
+    /// a copy. Writes stay local, overriding the base's field for this object only;
+    /// `remove` drops the override, and the base's field shows through again.
+    ///
+    /// A base field is copied into the local map the first time it's reached through a
+    /// `&mut` reference, by `get`, `lookup` or `get_path` from host code, or by a script
+    /// assigning into it such as `stats.hp = 3`, since the caller may write through it.
+    /// Until then, scripts reading it, even along a path such as `stats.hp`, read the
+    /// base's and leave the local map alone.
+    ///
+    /// The base is `'static` and behind an `Arc` rather than borrowed for `'world` like the
+    /// parent: it's never written, so it needs no unique borrow, and it can outlive any one
+    /// world. Build it from a proxy-free object, for example `Arc::new(t.resolve()?.deep_clone())`;
+    /// only its local map and its own base are consulted. Serialization and `==` skip base
+    /// fields the object hasn't copied, like proxied ones; `resolve` includes them.
+    /// Without the `sync` feature objects aren't `Send`, so bases are shared within a thread.
+    pub fn set_base(&mut self, base: Arc<TalkObject<'static>>) { self.base = Some(base) }
+
+    /// The base chain, nearest first.
+    fn bases(&self) -> impl Iterator<Item = &TalkObject<'static>> {
+        std::iter::successors(self.base.as_deref(), |b| b.base.as_deref())
+    }
+
+    /// Like `get`, but never inserts missing fields.
+    pub fn lookup(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
+        find(&mut self.scopes, &mut self.proxies, &mut self.map, &self.base, &mut self.parent, name)
+    }
+
+    /// Follows a dotted path such as `"player.stats.hp"` through nested objects, like a
+    /// script reading `player.stats.hp` would. `None` if any segment is missing or an
+    /// intermediate isn't an object. Never inserts missing fields.
+    pub fn get_path(&mut self, path: &str) -> Option<&mut TalkValue<'world>> {
+        let mut segments = path.split('.');
+        let mut value = self.lookup(segments.next()?)?;
+        for name in segments {
+            value = match value {
+                TalkValue::Object(o) => o.lookup(name)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// A copy of the value at a dotted path, or `default` wherever `get_path` finds nothing.
+    /// A field that holds `Null` is returned as `Null`. Like `get_path`, never inserts
+    /// missing fields, even into an auto-vivifying object, and as it only reads, it doesn't
+    /// copy base fields into the local map either.
+    pub fn get_or(&mut self, path: &str, default: TalkValue<'world>) -> TalkValue<'world> {
+        let mut segments = path.split('.');
+        let mut found = segments.next().and_then(|name| self.read(name));
+        for name in segments {
+            found = found.and_then(|value| value.field(name));
+        }
+        found.map_or(default, |value| value.into_cow().into_owned())
+    }
+
+    /// Evaluates each source with `eval_str`, in order, and returns one result per source, so
+    /// a source that fails to parse or evaluate doesn't stop the ones after it. Meant for
+    /// independent expressions such as a screen of choice conditions: they share this
+    /// context, so assignments carry over, including those a failing source made before
+    /// it failed.
+    pub fn eval_all(&mut self, srcs: &[&str]) -> Vec<Result<TalkValue<'world>, TalkEvalError>> {
+        srcs.iter().map(|src| eval_str(src, self)).collect()
+    }
+
+    /// Stores `val` at a dotted path such as `"player.stats.hp"`, creating missing
+    /// intermediate objects. Fails, naming the path up to the failing segment, if an
+    /// intermediate holds something other than an object.
+    pub fn set_path(&mut self, path: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+        if path.split('.').any(str::is_empty) {
+            return Err(TalkEvalError::new(&format!("Invalid path {:?}", path)));
+        }
+        let (parents, name) = match path.rfind('.') {
+            Some(dot) => (&path[..dot], &path[dot + 1..]),
+            None => return self.set(path, val),
+        };
+
+        let mut object: &mut TalkObject<'world> = self;
+        let mut end = 0;
+        for segment in parents.split('.') {
+            end += segment.len();
+            if object.lookup(segment).is_none() {
+                object.set(segment, TalkValue::new_obj())?;
+            }
+            object = match object.lookup(segment) {
+                Some(TalkValue::Object(o)) => o,
+                Some(other) => return Err(TalkEvalError::new(&format!("{} is not an object, got {}", &path[..end], other.type_name()))),
+                None => return Err(TalkEvalError::new(&format!("{} is not an object, got null", &path[..end]))),
+            };
+            end += 1;
+        }
+        object.set(name, val)
+    }
+
+    /// Whether a read of `name` finds anything, without copying it from the base.
+    fn resolves(&mut self, name: &str) -> bool { self.read(name).is_some() }
+
+    /// Like `lookup`, but a field only the base has is borrowed from the base: a read
+    /// doesn't need a local copy of it.
+    fn read(&mut self, name: &str) -> Option<Found<'_, 'world>> {
+        let local = self.scopes.contains(name) || self.map.contains_key(name);
+        if !local && shared(&self.base, name).is_some() && !self.proxies.iter_mut().any(|p| p.has(name)) {
+            return shared(&self.base, name).map(Found::Shared);
+        }
+        self.lookup(name).map(Found::Local)
+    }
+
+    /// The field, borrowed, or an owned `Null` if missing. A base field is copied out, not
+    /// into the local map.
+    fn lookup_or_null(&mut self, name: &str) -> Cow<'_, TalkValue<'world>> {
+        self.read(name).map_or(Cow::Owned(TalkValue::Null), Found::into_cow)
+    }
+
+    /// Number of fields in the local `map`; fields behind a proxy are not counted.
+    pub fn local_len(&self) -> usize { self.map.len() }
+
+    /// Local fields as compact JSON. Proxied fields are not included; serialize `resolve()`'s
+    /// result for those.
+    pub fn to_json(&self) -> Result<String, TalkEvalError> {
+        serde_json::to_string(self).map_err(|e| TalkEvalError::new(&e.to_string()))
+    }
+
+    /// Like `to_json`, but indented for reading.
+    pub fn to_json_pretty(&self) -> Result<String, TalkEvalError> {
+        serde_json::to_string_pretty(self).map_err(|e| TalkEvalError::new(&e.to_string()))
+    }
+
+    /// A proxy-free copy with every field a proxy exposes through `keys()` materialized,
+    /// recursively. Use it to serialize world state that lives behind proxies, which plain
+    /// serialization skips. Proxy fields take precedence over local ones of the same name.
+    ///
+    /// Fails on structures nested deeper than `MAX_RESOLVE_DEPTH`, which is how a proxy
+    /// that keeps producing new objects (a cycle, in effect) is caught.
+    pub fn resolve(&mut self) -> Result<TalkObject<'world>, TalkEvalError> {
+        self.resolve_at(0)
+    }
+
+    /// Compares what both objects hold once their proxies are resolved, so a field stored
+    /// locally equals the same field served by a proxy. Unlike `==`, which compares the
+    /// local maps only, this sees everything `resolve` does, and nothing it doesn't:
+    /// proxies that can't enumerate their keys are skipped.
+    ///
+    /// Only names that `keys` lists are read, so an auto-vivifying object or proxy gains no
+    /// fields from the comparison. Objects nested too deep to resolve compare unequal.
+    pub fn eq_resolved(&mut self, other: &mut Self) -> bool {
+        match (self.resolve(), other.resolve()) {
+            (Ok(l), Ok(r)) => l == r,
+            _ => false,
+        }
+    }
+
+    /// A self-contained copy of the local `map`, nested objects included, with the same
+    /// settings as `clone`.
+    ///
+    /// `'world` only ever appears in the proxies and the parent, which borrow the world;
+    /// plain values own everything they hold. A copy without those borrows isn't tied to
+    /// the world at all, so it is returned as `TalkObject<'static>` and can outlive it, for
+    /// example as a save snapshot. To keep proxied state in the copy, `resolve` first:
+    /// `world.resolve()?.deep_clone()`.
+    ///
+    /// The base, if any, is shared with the copy rather than copied.
+    pub fn deep_clone(&self) -> TalkObject<'static> { self.detached() }
+
+    fn detached<'a>(&self) -> TalkObject<'a> {
+        let map = self.map.iter().map(|(k, v)| (k.clone(), v.detached())).collect();
+        TalkObject { map, base: self.base.clone(), ..self.empty_with_settings() }
+    }
+
+    /// Layers `other`'s fields over this object's: each one is inserted, replacing a field of
+    /// the same name. See `merge_deep` to merge nested objects instead of replacing them.
+    ///
+    /// Only local `map`s take part: `other`'s proxies, parent and settings are dropped, and
+    /// a field is written here even if one of this object's proxies also serves it.
+    pub fn merge(&mut self, other: TalkObject<'world>) {
+        self.merge_fields(other, false)
+    }
+
+    /// Like `merge`, except that where both objects hold an object under the same name, the
+    /// two are merged recursively, so `{ stats: { hp: 1, mp: 2 } }` merged with
+    /// `{ stats: { hp: 5 } }` keeps `mp`. Any other pair of values, such as two lists, is
+    /// still replaced wholesale.
+    pub fn merge_deep(&mut self, other: TalkObject<'world>) {
+        self.merge_fields(other, true)
+    }
+
+    fn merge_fields(&mut self, other: TalkObject<'world>, deep: bool) {
+        for (name, value) in other.map {
+            match (self.map.get_mut(&name), value) {
+                (Some(TalkValue::Object(mine)), TalkValue::Object(theirs)) if deep => mine.merge_fields(*theirs, true),
+                (_, value) => { self.map.insert(name, value); },
+            }
+        }
+    }
+
+    /// The changes that turn this object into `other`, as an object with default settings:
+    /// fields added or changed in `other` hold their new value, and removed fields hold
+    /// `Null`. Where both sides hold an object and it changed, the field holds the diff of
+    /// the two, recursively; unchanged fields are left out, so equal objects diff to `{}`.
+    ///
+    /// `self.merge_deep(self.diff(&other))` then brings this object up to date with `other`,
+    /// except that removed fields remain, set to `Null`, which scripts can't tell apart from
+    /// a missing field. Storing `Null` in `other` reads as a removal for the same reason.
+    /// Only local `map`s are compared; `resolve` both sides first to include proxied fields.
+    pub fn diff(&self, other: &Self) -> TalkObject<'world> {
+        let mut changes = TalkObject::new();
+        for (name, old) in &self.map {
+            let change = match (old, other.map.get(name)) {
+                (old, Some(new)) if old == new => continue,
+                (TalkValue::Object(old), Some(TalkValue::Object(new))) => TalkValue::Object(Box::new(old.diff(new))),
+                (_, Some(new)) => new.clone(),
+                (_, None) => TalkValue::Null,
+            };
+            changes.map.insert(name.clone(), change);
+        }
+        for (name, new) in &other.map {
+            if !self.map.contains_key(name) {
+                changes.map.insert(name.clone(), new.clone());
+            }
+        }
+        changes
+    }
+
+    /// An object without fields, but with this one's settings. Proxies, the parent,
+    /// registered functions, `on_set` callbacks and block-local variables are not settings.
+    fn empty_with_settings<'a>(&self) -> TalkObject<'a> {
+        TalkObject {
+            auto_vivify: self.auto_vivify,
+            loose_equality: self.loose_equality,
+            max_iterations: self.max_iterations,
+            max_depth: self.max_depth,
+            max_eval_depth: self.max_eval_depth,
+            step_budget: self.step_budget,
+            collect_warnings: self.collect_warnings,
+            ..Default::default()
+        }
+    }
+
+    fn resolve_at(&mut self, depth: usize) -> Result<TalkObject<'world>, TalkEvalError> {
+        if depth > MAX_RESOLVE_DEPTH {
+            return Err(TalkEvalError::new(&format!("Objects nested deeper than {} levels", MAX_RESOLVE_DEPTH)));
+        }
+        let mut map = HashMap::new();
+        // Lowest priority first, so that nearer bases, then local fields, overwrite.
+        let bases: Vec<&TalkObject<'static>> = self.bases().collect();
+        for base in bases.into_iter().rev() {
+            for (k, v) in base.map.iter() {
+                map.insert(k.clone(), resolve_value(&mut v.detached(), depth)?);
+            }
+        }
+        for (k, v) in self.map.iter_mut() {
+            map.insert(k.clone(), resolve_value(v, depth)?);
+        }
+        // Lowest priority first, so that higher priority proxies overwrite.
+        for p in self.proxies.iter_mut().rev() {
+            for k in p.keys() {
+                if let Some(v) = p.get(&k) {
+                    let v = resolve_value(v, depth)?;
+                    map.insert(intern::key(&k), v);
+                }
+            }
+        }
+        Ok(TalkObject { map, ..self.empty_with_settings() })
+    }
+
+}
+
+/// Block-local variables, the proxies in priority order, then the local map, then the base
+/// chain, then the parent chain. Takes the fields separately so the borrows of each stay
+/// disjoint.
+fn find<'a, 'world>(
+    scopes: &'a mut ScopeStack<'world>,
+    proxies: &'a mut [&'world mut dyn TalkObjectProxy<'world>],
+    map: &'a mut HashMap<Key, TalkValue<'world>>,
+    base: &Option<Arc<TalkObject<'static>>>,
+    parent: &'a mut Option<&'world mut TalkObject<'world>>,
+    name: &str,
+) -> Option<&'a mut TalkValue<'world>> {
+    if let Some(v) = scopes.get_mut(name) {
+        return Some(v);
+    }
+    for p in proxies.iter_mut() {
+        if let Some(v) = p.get(name) {
+            return Some(v);
+        }
+    }
+    copy_shared(map, base, name);
+    match map.get_mut(name) {
+        Some(v) => Some(v),
+        None => parent.as_mut().and_then(|p| p.lookup(name)),
+    }
+}
+
+/// The field of the nearest base in the chain that has `name`.
+fn shared<'a>(base: &'a Option<Arc<TalkObject<'static>>>, name: &str) -> Option<&'a TalkValue<'static>> {
+    std::iter::successors(base.as_deref(), |b| b.base.as_deref()).find_map(|b| b.map.get(name))
+}
+
+/// A value found by a read, borrowed either from the context or from a base it shares.
+enum Found<'a, 'world> {
+    Local(&'a mut TalkValue<'world>),
+    Shared(&'a TalkValue<'static>),
+}
+
+impl<'a, 'world> Found<'a, 'world> {
+    /// The field `name` of this value, if it's an object that has one.
+    fn field(self, name: &str) -> Option<Found<'a, 'world>> {
+        match self {
+            Found::Local(TalkValue::Object(o)) => o.read(name),
+            Found::Shared(TalkValue::Object(o)) => o.map.get(name).or_else(|| shared(&o.base, name)).map(Found::Shared),
+            _ => None,
+        }
+    }
+
+    fn is_null(&self) -> bool { matches!(self, Found::Local(TalkValue::Null) | Found::Shared(TalkValue::Null)) }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Found::Local(v) => v.type_name(),
+            Found::Shared(v) => v.type_name(),
+        }
+    }
+
+    /// The value, still borrowed if it's local; a base value is detached from the base.
+    fn into_cow(self) -> Cow<'a, TalkValue<'world>> {
+        match self {
+            Found::Local(v) => Cow::Borrowed(v),
+            Found::Shared(v) => Cow::Owned(v.detached()),
+        }
+    }
+}
+
+/// Copies a base field into the local map, unless it's there already, before handing out a
+/// `&mut` to it.
+fn copy_shared<'world>(map: &mut HashMap<Key, TalkValue<'world>>, base: &Option<Arc<TalkObject<'static>>>, name: &str) {
+    if !map.contains_key(name) {
+        if let Some(v) = shared(base, name) {
+            map.insert(intern::key(name), v.detached());
+        }
+    }
+}
+
+fn resolve_value<'world>(val: &mut TalkValue<'world>, depth: usize) -> Result<TalkValue<'world>, TalkEvalError> {
+    match val {
+        TalkValue::Object(o) => Ok(TalkValue::Object(Box::new(o.resolve_at(depth + 1)?))),
+        TalkValue::List(l) => Ok(TalkValue::List(l.iter_mut().map(|v| resolve_value(v, depth + 1)).collect::<Result<_, _>>()?)),
+        other => Ok(other.clone()),
+    }
+}
+
+/// An AST node and the region of source it was parsed from, see `parser::parse_spanned`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+/// A region of the source: byte offsets plus the 1-based line and column of `start`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TalkEvalErrorKind {
+    /// `got` is a `TalkValue::type_name`; `expected` is one or a list of them.
+    TypeMismatch { expected: &'static str, got: &'static str },
+    UndefinedField(String),
+    DivisionByZero,
+    ParseError(String),
+    IndexOutOfBounds(usize),
+    UndefinedFunction(String),
+    /// A write refused by a read-only proxy.
+    ReadOnly(String),
+    /// Integer overflow in the given operation.
+    Overflow(String),
+    /// Anything without a dedicated kind, see `TalkEvalError::new`.
+    Message(String),
+}
+
+impl Display for TalkEvalErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TypeMismatch { expected, got } => write!(f, "expected {}, got {}", expected, got),
+            Self::UndefinedField(name) => write!(f, "Undefined field {}", name),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::ParseError(text) => write!(f, "{}", text),
+            Self::IndexOutOfBounds(i) => write!(f, "Index {} out of bounds", i),
+            Self::UndefinedFunction(name) => write!(f, "Undefined function {}", name),
+            Self::ReadOnly(name) => write!(f, "Field {} is read-only", name),
+            Self::Overflow(op) => write!(f, "Integer overflow in {}", op),
+            Self::Message(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+pub struct TalkEvalError {
+    pub kind: TalkEvalErrorKind,
+    /// Always set by the parser; set during evaluation only if the failing node knows its origin.
+    pub span: Option<Span>,
+}
+
+impl Debug for TalkEvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "error at {}:{}: {}", span.line, span.col, self.kind),
+            None => write!(f, "error: {}", self.kind),
+        }
+    }
+}
+
+impl Display for TalkEvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "error at {}:{}: {}", span.line, span.col, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for TalkEvalError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TalkEvalWarningKind {
+    /// An identifier that named no variable or field, read as `Null`.
+    UndefinedVariable(String),
+    /// `==` or `!=` between values of different types, coerced under `loose_equality`.
+    /// Comparing an `Int` with a `Float` is not a coercion and isn't reported.
+    LooseEquality { lhs: &'static str, rhs: &'static str },
+}
+
+impl Display for TalkEvalWarningKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UndefinedVariable(name) => write!(f, "Read undefined variable {} as null", name),
+            Self::LooseEquality { lhs, rhs } => write!(f, "Loosely compared {} with {}", lhs, rhs),
+        }
+    }
+}
+
+/// Something suspicious that evaluation went on past, see `TalkObjectBuilder::collect_warnings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TalkEvalWarning {
+    pub kind: TalkEvalWarningKind,
+    /// Set like `TalkEvalError::span`: to the innermost spanned node being evaluated, if any.
+    pub span: Option<Span>,
+}
+
+impl Display for TalkEvalWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "warning at {}:{}: {}", span.line, span.col, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl From<TalkEvalErrorKind> for TalkEvalError {
+    fn from(kind: TalkEvalErrorKind) -> Self { TalkEvalError { kind, span: None } }
+}
+
+impl TalkEvalError {
+    // pub fn new(text: String) -> Self { TalkEvalError{ text } }
+    pub fn new(text: &str) -> Self { TalkEvalErrorKind::Message(text.to_string()).into() }
+
+    /// The message without the span.
+    pub fn text(&self) -> String { self.kind.to_string() }
+
+    pub fn with_span(self, span: Span) -> Self { TalkEvalError { span: Some(span), ..self } }
+
+    /// The message followed by the line of `src` the error points at, with the span
+    /// underlined in carets, for showing to script authors:
+    ///
+    /// ```text
+    /// error at 2:4: Mul expects int operands, got string
+    ///   |
+    /// 2 |   (2 * "a")
+    ///   |    ^^^^^^^
+    /// ```
+    ///
+    /// A span over several lines is underlined to the end of its first line. Without a span,
+    /// or if the span lies outside `src`, it's just the message.
+    pub fn render(&self, src: &str) -> String {
+        let span = match self.span {
+            Some(span) if src.is_char_boundary(span.start) => span,
+            _ => return self.to_string(),
+        };
+        let line_start = src[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[span.start..].find('\n').map_or(src.len(), |i| span.start + i);
+        let line = src[line_start..line_end].trim_end_matches('\r');
+        // Tabs are kept so that the carets line up however wide the terminal draws them.
+        let indent: String = src[line_start..span.start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let width = src.get(span.start..span.end.min(line_start + line.len())).map_or(0, |s| s.chars().count()).max(1);
+
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!("{}\n{} |\n{} | {}\n{} | {}{}", self, gutter, number, line, gutter, indent, "^".repeat(width))
+    }
+}
+
+/// Nodes that compute something (arithmetic, comparisons, calls) return an owned value,
+/// while reading a field borrows it from the context, so pure reads don't clone. The context
+/// is only modified by writes: assignments and loop variables.
+///
+/// A borrowed result keeps the context borrowed, so a node evaluating several children
+/// turns the results it keeps into owned values before evaluating the next child.
+type EvalResult<'ctx, 'world> = Result<Cow<'ctx, TalkValue<'world>>, TalkEvalError>;
+
+pub trait Eval<'world> {
+    fn eval<'ctx>(&self, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world>;
+}
+
+/// Parses and evaluates `src` in one go. Evaluation errors carry the span of the operator,
+/// index or call that raised them, where known. `src` may nest as deeply as the context's
+/// `max_eval_depth`, if it was set.
+pub fn eval_str<'world>(src: &str, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    parse_spanned_with_limit(src, context.max_parse_depth())?.eval(context).map(Cow::into_owned)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Null,
+}
+
+impl Literal {
+    pub fn to_value<'world>(&self) -> TalkValue<'world> {
+        match self {
+            Literal::Int(i) => TalkValue::Int(*i),
+            Literal::Float(x) => TalkValue::Float(*x),
+            Literal::String(s) => TalkValue::String(s.clone()),
+            Literal::Bool(b) => TalkValue::Bool(*b),
+            Literal::Null => TalkValue::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    /// Adds two `Int`s. If either operand is a `String`, both are concatenated instead:
+    ///
+    /// | lhs, rhs           | String | Int/Float/Bool | Null/List/Object |
+    /// |--------------------|--------|----------------|------------------|
+    /// | String             | concat | concat         | error            |
+    /// | Int/Float/Bool     | concat | (arithmetic)   | (arithmetic)     |
+    /// | Null/List/Object   | error  | (arithmetic)   | (arithmetic)     |
+    ///
+    /// The non-string operand is rendered with `Display`, so `"HP: " + 100` is `"HP: 100"`.
+    Add,
+    Sub,
+    Mul,
+    /// Truncating division, like Rust's `/`: `-7 / 2 == -3`.
+    Div,
+    /// Remainder of truncating division, like Rust's `%`; the result takes the sign
+    /// of the dividend: `-7 % 2 == -1`, `7 % -2 == 1`.
+    Mod,
+    /// Bitwise operators, on the two's complement of `Int`s. Unlike the arithmetic
+    /// operators, they accept nothing but `Int`s.
+    BitAnd,
+    BitOr,
+    BitXor,
+    /// Shifts by 0 to 63 bits; any other amount is an error. Bits shifted out are dropped
+    /// without an overflow error, so `1 << 63` is `i64::MIN`.
+    Shl,
+    /// Arithmetic shift, filling with the sign bit: `-8 >> 1 == -4`, `-1 >> 63 == -1`.
+    /// Amounts are checked like `Shl`'s.
+    Shr,
+}
+
+impl BinOp {
+    /// The operator as written in Talk source.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+        }
+    }
+
+    fn apply<'world>(self, l: &TalkValue<'world>, r: &TalkValue<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+        match (self, l, r) {
+            (BinOp::Add, TalkValue::String(_), _) | (BinOp::Add, _, TalkValue::String(_)) => concat(l, r),
+            (BinOp::BitAnd, ..) | (BinOp::BitOr, ..) | (BinOp::BitXor, ..) | (BinOp::Shl, ..) | (BinOp::Shr, ..) => match (l, r) {
+                (TalkValue::Int(a), TalkValue::Int(b)) => Ok(TalkValue::Int(self.apply_int(*a, *b)?)),
+                (TalkValue::Int(_), other) | (other, _) => Err(TalkEvalError::new(
+                    &format!("{:?} expects int operands, got {}", self, other.type_name()))),
+            },
+            _ => Ok(TalkValue::Int(self.apply_int(int_operand(self, l)?, int_operand(self, r)?)?)),
+        }
+    }
+
+    /// Checked, so overflowing scripts fail instead of panicking or wrapping.
+    fn apply_int(self, l: i64, r: i64) -> Result<i64, TalkEvalError> {
+        let result = match self {
+            BinOp::Div | BinOp::Mod if r == 0 => return Err(TalkEvalErrorKind::DivisionByZero.into()),
+            BinOp::Shl | BinOp::Shr if !(0..64).contains(&r) =>
+                return Err(TalkEvalError::new(&format!("Shift amount must be between 0 and 63, got {}", r))),
+            BinOp::Add => l.checked_add(r),
+            BinOp::Sub => l.checked_sub(r),
+            BinOp::Mul => l.checked_mul(r),
+            // i64::MIN / -1
+            BinOp::Div => l.checked_div(r),
+            BinOp::Mod => l.checked_rem(r),
+            BinOp::BitAnd => Some(l & r),
+            BinOp::BitOr => Some(l | r),
+            BinOp::BitXor => Some(l ^ r),
+            BinOp::Shl => Some(l << r),
+            BinOp::Shr => Some(l >> r),
+        };
+        result.ok_or_else(|| TalkEvalErrorKind::Overflow(format!("{} {} {}", l, self.symbol(), r)).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    /// The operator as written in Talk source.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+
+    /// `Eq`/`Ne` are defined for any pair of values; the ordering operators fail on
+    /// values that `TalkValue::try_cmp` does not order. Unlike `TalkValue`'s `PartialEq`,
+    /// both compare an `Int` and a `Float` by value, so `1 == 1.0`, and with `loose` they
+    /// follow `coerce::loose_eq`.
+    fn apply<'world>(self, l: &TalkValue<'world>, r: &TalkValue<'world>, loose: bool) -> Result<bool, TalkEvalError> {
+        let equal = || if loose {
+            coerce::loose_eq(l, r)
+        } else {
+            coerce::compare_numbers(l, r).map_or_else(|| l == r, |o| o == Ordering::Equal)
+        };
+        match self {
+            CmpOp::Eq => Ok(equal()),
+            CmpOp::Ne => Ok(!equal()),
+            CmpOp::Lt => Ok(l.try_cmp(r)? == Ordering::Less),
+            CmpOp::Le => Ok(l.try_cmp(r)? != Ordering::Greater),
+            CmpOp::Gt => Ok(l.try_cmp(r)? == Ordering::Greater),
+            CmpOp::Ge => Ok(l.try_cmp(r)? != Ordering::Less),
+        }
+    }
+}
+
+fn concat<'world>(l: &TalkValue<'world>, r: &TalkValue<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    for v in [l, r] {
+        if let TalkValue::Null | TalkValue::List(_) | TalkValue::Object(_) = v {
+            return Err(TalkEvalError::new(&format!("cannot concatenate {} and {}", l.type_name(), r.type_name())));
+        }
+    }
+    Ok(TalkValue::String(coerce::to_string(l) + &coerce::to_string(r)))
+}
+
+fn int_operand(op: BinOp, val: &TalkValue) -> Result<i64, TalkEvalError> {
+    coerce::to_number(val).map_err(|_| TalkEvalError::new(
+        &format!("{:?} expects int operands, got {}", op, val.type_name())))
+}
+
+/// The AST of a Talk expression, as built by `parser::parse`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Dummy,
+    Literal(Literal),
+    /// Reads a field of the context; undefined fields evaluate to `Null`.
+    Identifier(String),
+    /// `base.name`; `base` must evaluate to an `Object`. Undefined fields evaluate to `Null`.
+    Field {
+        base: Box<Expression>,
+        name: String,
+    },
+    /// `base?.name`: like `Field`, but evaluates to `Null` instead of failing when `base` is
+    /// `Null` or undefined. Only this step is guarded, so `a?.b.c` still fails if `a` is
+    /// `Null`; write `a?.b?.c` to guard each step. Never creates missing fields.
+    OptionalField {
+        base: Box<Expression>,
+        name: String,
+    },
+    /// `base[index]`: an `Int` index into a `List`, or a `String` key into an `Object`.
+    /// Undefined keys evaluate to `Null`, as with `Field`, but list indices must be in range.
+    ///
+    /// `index` is evaluated before `base`, so that the element can be borrowed from the
+    /// context like a field is.
+    Index {
+        base: Box<Expression>,
+        index: Box<Expression>,
+    },
+    Binary {
+        op: BinOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Compare {
+        op: CmpOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `needle in haystack`: whether a `List` has an element equal to `needle`, an `Object`
+    /// has the key `needle`, or a `String` contains `needle` as a substring.
+    In {
+        needle: Box<Expression>,
+        haystack: Box<Expression>,
+    },
+    /// Evaluates to the first falsy operand, or the last one; `rhs` only runs if needed.
+    And {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// Evaluates to the first truthy operand, or the last one; `rhs` only runs if needed.
+    Or {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `lhs xor rhs`: whether exactly one operand is truthy, as a `Bool`. Both operands are
+    /// always evaluated, since the result depends on each of them.
+    Xor {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `lhs ?? rhs`: `lhs` unless it is `Null`, in which case `rhs` is evaluated. Unlike `Or`,
+    /// falsy values such as `0` or `""` are kept.
+    Coalesce {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Not {
+        operand: Box<Expression>,
+    },
+    Neg {
+        operand: Box<Expression>,
+    },
+    /// `~operand`: flips every bit of an `Int`, so `~x == -x - 1`: `~0 == -1`, `~-1 == 0`
+    /// and `~5 == -6`. Never overflows.
+    BitNot {
+        operand: Box<Expression>,
+    },
+    /// `typeof operand`: the `TalkValue::type_name` of its value, as a `String`.
+    TypeOf {
+        operand: Box<Expression>,
+    },
+    /// `cond ? then_branch : else_branch`; only the taken branch is evaluated.
+    Conditional {
+        cond: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+    },
+    /// Statement-style branch. Evaluates to `Null` when `cond` is falsy and there is no `else_block`.
+    If {
+        cond: Box<Expression>,
+        then_block: Box<Expression>,
+        else_block: Option<Box<Expression>>,
+    },
+    /// Evaluates `scrutinee`, then each arm's pattern in order, and evaluates the body of the
+    /// first pattern equal to it (by `PartialEq`, regardless of `loose_equality`), else
+    /// `default`, else `Null`. Patterns after the match and all other bodies don't run.
+    Match {
+        scrutinee: Box<Expression>,
+        arms: Vec<(Expression, Expression)>,
+        default: Option<Box<Expression>>,
+    },
+    /// Runs `body` while `cond` is truthy and evaluates to the last body value,
+    /// or `Null` if the body never ran. Fails after `TalkObject::max_iterations` iterations.
+    While {
+        cond: Box<Expression>,
+        body: Box<Expression>,
+    },
+    /// Runs `body` once per element of the `List` that `iterable` evaluates to, with the
+    /// element bound to `var`. Evaluates to the last body value, or `Null` for an empty list.
+    ///
+    /// `var` is bound in a frame of its own (see `ScopeStack`), shadowing any field of the
+    /// same name while the loop runs and disappearing afterwards.
+    ForIn {
+        var: String,
+        iterable: Box<Expression>,
+        body: Box<Expression>,
+    },
+    /// `start..end` or `start..=end`: the `Int`s from `start` up to `end`, as a `List`, so a
+    /// `ForIn` over a range is a loop over a list. Empty if `end` comes before `start`.
+    /// Ranges longer than `TalkObject::max_iterations` are rejected.
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    },
+    /// `[a, b, c]`: evaluates each element in order into a `List`.
+    List(Vec<Expression>),
+    /// `{ name: "Bob", hp: 100 }`: evaluates each value in order into a new `Object`. The
+    /// parser rejects duplicate keys; if a hand-built tree repeats one, the last value wins.
+    Object(Vec<(String, Expression)>),
+    /// `{ a; b; c }`: evaluates each expression in order and evaluates to the last value,
+    /// or `Null` when empty. Assignments inside a block write to the context as usual, and
+    /// `let` binds locals that end with the block; see `ScopeStack`.
+    Block(Vec<Expression>),
+    /// `defined(path)`: whether an `Identifier`, `Field` or `OptionalField` path leads to a
+    /// value other than `Null`. Missing steps are `false` rather than errors, and nothing is
+    /// created, even in an auto-vivifying context. Parsed from a call, since it takes the
+    /// path itself rather than its value, so a function registered as `defined` can't shadow it.
+    Defined {
+        path: Box<Expression>,
+    },
+    /// Calls a function registered with `TalkObject::register_fn`, or else a builtin
+    /// (see the `builtins` module), after evaluating `args` in order.
+    Call {
+        name: String,
+        args: Vec<Expression>,
+    },
+    /// Evaluates like `node`. Errors without a span that `node` raises, or its children,
+    /// get this one, so they point at the innermost spanned node that failed.
+    Spanned(Box<Spanned<Expression>>),
+    /// Stores the value into `target`, which must be an `Identifier` or a `Field`,
+    /// and evaluates to the stored value.
+    Assign {
+        target: Box<Expression>,
+        value: Box<Expression>,
+    },
+    /// `let name = value`: binds `name` in the innermost scope frame, shadowing fields and
+    /// outer bindings, and evaluates to the bound value. With no frame to bind in it is an error.
+    Let {
+        name: String,
+        value: Box<Expression>,
+    },
+}
+
+impl Expression {
+    /// Source-like name of an assignable expression, for error messages.
+    fn describe(&self) -> String {
+        match self {
+            Expression::Identifier(name) => name.clone(),
+            Expression::Field { base, name } => format!("{}.{}", base.describe(), name),
+            Expression::OptionalField { base, name } => format!("{}?.{}", base.describe(), name),
+            _ => "expression".to_string(),
+        }
+    }
+
+    /// Whether this is a chain of field accesses on an identifier, which can be evaluated
+    /// by borrowing from the context rather than copying.
+    pub(crate) fn is_path(&self) -> bool {
+        match self {
+            Expression::Identifier(_) => true,
+            Expression::Field { base, .. } | Expression::OptionalField { base, .. } => base.is_path(),
+            _ => false,
+        }
+    }
+
+    /// Number of field accesses chained in this path: 2 for `a.b.c`.
+    fn field_depth(&self) -> usize {
+        match self {
+            Expression::Field { base, .. } | Expression::OptionalField { base, .. } => 1 + base.field_depth(),
+            _ => 0,
+        }
+    }
+
+    fn check_depth(&self, context: &TalkObject) -> Result<(), TalkEvalError> {
+        let max = context.max_depth();
+        if self.field_depth() > max {
+            return Err(TalkEvalError::new(&format!("Field path is nested deeper than {} levels", max)));
+        }
+        Ok(())
+    }
+
+    /// The value an `Identifier`, `Field` or `OptionalField` path refers to, borrowed from
+    /// the context. `None` if undefined, or if an `OptionalField` step found `Null`.
+    fn lookup_path<'ctx, 'world>(&self, context: &'ctx mut TalkObject<'world>) -> Result<Option<&'ctx mut TalkValue<'world>>, TalkEvalError> {
+        Ok(match self {
+            Expression::Identifier(name) => context.lookup(name),
+            Expression::Field { base, name } => base.eval_object(context)?.lookup(name),
+            Expression::OptionalField { base, name } => base.eval_optional_object(context)?.and_then(|o| o.lookup(name)),
+            other => return Err(TalkEvalError::new(&format!("cannot assign to {:?}", other))),
+        })
+    }
+
+    /// Like `lookup_path`, but for reading: a value the path reaches through a base stays
+    /// there rather than being copied into the local map.
+    fn read_path<'ctx, 'world>(&self, context: &'ctx mut TalkObject<'world>) -> Result<Option<Found<'ctx, 'world>>, TalkEvalError> {
+        let (base, name, optional) = match self {
+            Expression::Identifier(name) => return Ok(context.read(name)),
+            Expression::Field { base, name } => (base, name, false),
+            Expression::OptionalField { base, name } => (base, name, true),
+            _ => return Ok(None),
+        };
+        match base.read_path(context)? {
+            Some(found @ Found::Local(TalkValue::Object(_))) | Some(found @ Found::Shared(TalkValue::Object(_))) => Ok(found.field(name)),
+            found if optional && found.as_ref().is_none_or(Found::is_null) => Ok(None),
+            found => {
+                let type_name = found.map_or("null", |v| v.type_name());
+                Err(TalkEvalError::new(&format!("{} is not an object, got {}", base.describe(), type_name)))
+            }
+        }
+    }
+
+    /// Like `read_path`, but treats every step as optional, and a non-object step as missing.
+    fn read_defined<'ctx, 'world>(&self, context: &'ctx mut TalkObject<'world>) -> Option<Found<'ctx, 'world>> {
+        match self {
+            Expression::Identifier(name) => context.read(name),
+            Expression::Field { base, name } | Expression::OptionalField { base, name } => base.read_defined(context)?.field(name),
+            _ => None,
+        }
+    }
+
+    /// The object an `Identifier` or `Field` path refers to, borrowed from the context so
+    /// that it can be written to.
+    fn eval_object<'ctx, 'world>(&self, context: &'ctx mut TalkObject<'world>) -> Result<&'ctx mut TalkObject<'world>, TalkEvalError> {
+        match self.lookup_path(context)? {
+            Some(TalkValue::Object(o)) => Ok(o),
+            Some(other) => Err(TalkEvalError::new(&format!("{} is not an object, got {}", self.describe(), other.type_name()))),
+            None => Err(TalkEvalError::new(&format!("{} is not an object, got null", self.describe()))),
+        }
+    }
+
+    /// Like `eval_object`, but `None` where the path leads to `Null` or nowhere.
+    fn eval_optional_object<'ctx, 'world>(&self, context: &'ctx mut TalkObject<'world>) -> Result<Option<&'ctx mut TalkObject<'world>>, TalkEvalError> {
+        match self.lookup_path(context)? {
+            Some(TalkValue::Object(o)) => Ok(Some(o)),
+            Some(TalkValue::Null) | None => Ok(None),
+            Some(other) => Err(TalkEvalError::new(&format!("{} is not an object, got {}", self.describe(), other.type_name()))),
+        }
+    }
+}
+
+thread_local! {
+    /// Nodes being evaluated on this thread. A borrowed result keeps the context locked
+    /// until its node returns, so the count can't live in the context.
+    static EVAL_DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    /// Span of the innermost `Expression::Spanned` being evaluated on this thread, for
+    /// warnings. Kept out of the context for the same reason as `EVAL_DEPTH`.
+    static EVAL_SPAN: Cell<Option<Span>> = const { Cell::new(None) };
+}
+
+/// Makes `span` the current `EVAL_SPAN` for as long as it lives, then restores the outer one.
+struct SpanGuard(Option<Span>);
+
+impl SpanGuard {
+    fn enter(span: Span) -> Self { SpanGuard(EVAL_SPAN.with(|current| current.replace(Some(span)))) }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) { EVAL_SPAN.with(|current| current.set(self.0)) }
+}
+
+/// Counts one level of `EVAL_DEPTH` for as long as it lives, including while unwinding.
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter(max: usize) -> Result<Self, TalkEvalError> {
+        EVAL_DEPTH.with(|depth| {
+            if depth.get() >= max {
+                return Err(TalkEvalError::new(&format!("Maximum evaluation depth of {} exceeded", max)));
+            }
+            depth.set(depth.get() + 1);
+            Ok(DepthGuard)
+        })
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) { EVAL_DEPTH.with(|depth| depth.set(depth.get() - 1)) }
+}
+
+impl<'world> Eval<'world> for Expression {
+    /// Nodes that do more than pass a child's result on are evaluated by functions of
+    /// their own, so that the frame every nesting level puts on the stack stays small.
+    fn eval<'ctx>(&self, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+        // A span is bookkeeping, not a node: it takes no depth or step of its own.
+        if let Expression::Spanned(spanned) = self {
+            return eval_spanned(spanned, context);
+        }
+        let _depth = DepthGuard::enter(context.max_eval_depth())?;
+        context.take_step()?;
+        match self {
+            Expression::Dummy => context
+                .get("43")
+                .map(|v| Cow::Borrowed(&*v))
+                .ok_or_else(|| TalkEvalErrorKind::UndefinedField("43".to_string()).into()),
+            Expression::Literal(lit) => Ok(Cow::Owned(lit.to_value())),
+            Expression::Identifier(name) => eval_identifier(name, context),
+            Expression::Field { base, name } => self.eval_field(base, name, context),
+            Expression::OptionalField { base, name } => self.eval_optional_field(base, name, context),
+            Expression::Index { base, index } => eval_index(base, index, context),
+            Expression::Binary { op, lhs, rhs } => eval_binary(*op, lhs, rhs, context).map(Cow::Owned),
+            Expression::Compare { op, lhs, rhs } => eval_compare(*op, lhs, rhs, context).map(Cow::Owned),
+            Expression::In { needle, haystack } => eval_in(needle, haystack, context).map(Cow::Owned),
+            Expression::And { lhs, rhs } => {
+                let l = lhs.eval(context)?.into_owned();
+                if !coerce::to_bool(&l) {
+                    return Ok(Cow::Owned(l));
+                }
+                rhs.eval(context)
+            }
+            Expression::Or { lhs, rhs } => {
+                let l = lhs.eval(context)?.into_owned();
+                if coerce::to_bool(&l) {
+                    return Ok(Cow::Owned(l));
+                }
+                rhs.eval(context)
+            }
+            Expression::Xor { lhs, rhs } => {
+                let l = coerce::to_bool(&*lhs.eval(context)?);
+                Ok(Cow::Owned(TalkValue::Bool(l != coerce::to_bool(&*rhs.eval(context)?))))
+            }
+            Expression::Coalesce { lhs, rhs } => {
+                let l = lhs.eval(context)?.into_owned();
+                if !l.is_null() {
+                    return Ok(Cow::Owned(l));
+                }
+                rhs.eval(context)
+            }
+            Expression::Not { operand } => Ok(Cow::Owned(TalkValue::Bool(!coerce::to_bool(&*operand.eval(context)?)))),
+            Expression::Neg { operand } => eval_neg(operand, context).map(Cow::Owned),
+            Expression::BitNot { operand } => match *operand.eval(context)? {
+                TalkValue::Int(i) => Ok(Cow::Owned(TalkValue::Int(!i))),
+                ref other => Err(other.mismatch("int")),
+            },
+            Expression::TypeOf { operand } => Ok(Cow::Owned(TalkValue::new_str(operand.eval(context)?.type_name()))),
+            Expression::Conditional { cond, then_branch, else_branch } => {
+                if coerce::to_bool(&*cond.eval(context)?) {
+                    then_branch.eval(context)
+                } else {
+                    else_branch.eval(context)
+                }
+            }
+            Expression::If { cond, then_block, else_block } => {
+                if coerce::to_bool(&*cond.eval(context)?) {
+                    then_block.eval(context)
+                } else {
+                    match else_block {
+                        Some(block) => block.eval(context),
+                        None => Ok(Cow::Owned(TalkValue::Null)),
+                    }
+                }
+            }
+            Expression::Match { scrutinee, arms, default } => eval_match(scrutinee, arms, default.as_deref(), context),
+            Expression::While { cond, body } => eval_while(cond, body, context).map(Cow::Owned),
+            Expression::ForIn { var, iterable, body } => eval_for_in(var, iterable, body, context).map(Cow::Owned),
+            Expression::Range { start, end, inclusive } => eval_range(start, end, *inclusive, context).map(Cow::Owned),
+            Expression::List(items) => eval_list(items, context).map(Cow::Owned),
+            Expression::Object(entries) => eval_object(entries, context).map(Cow::Owned),
+            Expression::Block(statements) => eval_block(statements, context).map(Cow::Owned),
+            Expression::Defined { path } => Ok(Cow::Owned(TalkValue::Bool(path.read_defined(context).is_some_and(|v| !v.is_null())))),
+            Expression::Call { name, args } => eval_call(name, args, context).map(Cow::Owned),
+            Expression::Spanned(spanned) => eval_spanned(spanned, context),
+            Expression::Assign { target, value } => eval_assign(target, value, context),
+            Expression::Let { name, value } => eval_let(name, value, context),
+        }
+    }
+}
+
+impl Expression {
+    #[inline(never)]
+    fn eval_field<'ctx, 'world>(&self, base: &Expression, name: &str, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+        self.check_depth(context)?;
+        match base {
+            _ if base.is_path() => Ok(self.read_path(context)?.map_or(Cow::Owned(TalkValue::Null), Found::into_cow)),
+            _ => match base.eval(context)?.into_owned() {
+                TalkValue::Object(mut o) => Ok(Cow::Owned(o.lookup_or_null(name).into_owned())),
+                other => Err(TalkEvalError::new(&format!("expression is not an object, got {}", other.type_name()))),
+            },
+        }
+    }
+
+    #[inline(never)]
+    fn eval_optional_field<'ctx, 'world>(&self, base: &Expression, name: &str, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+        self.check_depth(context)?;
+        match base {
+            _ if base.is_path() => Ok(self.read_path(context)?.map_or(Cow::Owned(TalkValue::Null), Found::into_cow)),
+            _ => match base.eval(context)?.into_owned() {
+                TalkValue::Object(mut o) => Ok(Cow::Owned(o.lookup_or_null(name).into_owned())),
+                TalkValue::Null => Ok(Cow::Owned(TalkValue::Null)),
+                other => Err(TalkEvalError::new(&format!("expression is not an object, got {}", other.type_name()))),
+            },
+        }
+    }
+}
+
+#[inline(never)]
+fn eval_identifier<'ctx, 'world>(name: &str, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    if context.collect_warnings && !context.resolves(name) {
+        context.warn(TalkEvalWarningKind::UndefinedVariable(name.to_string()));
+        return Ok(Cow::Owned(TalkValue::Null));
+    }
+    Ok(context.lookup_or_null(name))
+}
+
+#[inline(never)]
+fn eval_spanned<'ctx, 'world>(spanned: &Spanned<Expression>, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    let _span = SpanGuard::enter(spanned.span);
+    spanned.node.eval(context).map_err(|e| match e.span {
+        Some(_) => e,
+        None => e.with_span(spanned.span),
+    })
+}
+
+#[inline(never)]
+fn eval_index<'ctx, 'world>(base: &Expression, index: &Expression, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    let index = index.eval(context)?.into_owned();
+    let found = match base {
+        _ if base.is_path() => {
+            base.check_depth(context)?;
+            base.read_path(context)?
+        }
+        _ => {
+            let mut value = base.eval(context)?.into_owned();
+            return index_into(&mut value, &index).map(|v| Cow::Owned(v.into_owned()));
+        }
+    };
+    match found {
+        Some(Found::Local(value)) => index_into(value, &index),
+        Some(Found::Shared(value)) => index_into(&mut value.detached(), &index).map(|v| Cow::Owned(v.into_owned())),
+        None => Err(TalkEvalError::new("cannot index null")),
+    }
+}
+
+fn index_into<'a, 'world>(value: &'a mut TalkValue<'world>, index: &TalkValue<'world>) -> Result<Cow<'a, TalkValue<'world>>, TalkEvalError> {
+    match (value, index) {
+        (TalkValue::List(l), TalkValue::Int(i)) => match usize::try_from(*i) {
+            Ok(i) => l.get(i).map(Cow::Borrowed).ok_or_else(|| TalkEvalErrorKind::IndexOutOfBounds(i).into()),
+            Err(_) => Err(TalkEvalError::new(&format!("Index {} out of bounds", i))),
+        },
+        (TalkValue::List(_), other) => Err(TalkEvalErrorKind::TypeMismatch { expected: "int", got: other.type_name() }.into()),
+        (TalkValue::Object(o), TalkValue::String(key)) => Ok(o.lookup_or_null(key)),
+        (TalkValue::Object(_), other) => Err(TalkEvalErrorKind::TypeMismatch { expected: "string", got: other.type_name() }.into()),
+        (other, _) => Err(TalkEvalError::new(&format!("cannot index {}", other.type_name()))),
+    }
+}
+
+#[inline(never)]
+fn eval_binary<'world>(op: BinOp, lhs: &Expression, rhs: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let l = lhs.eval(context)?.into_owned();
+    let r = rhs.eval(context)?;
+    op.apply(&l, &r)
+}
+
+#[inline(never)]
+fn eval_compare<'world>(op: CmpOp, lhs: &Expression, rhs: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let loose = context.loose_equality;
+    let l = lhs.eval(context)?.into_owned();
+    let r = rhs.eval(context)?;
+    let result = op.apply(&l, &r, loose)?;
+    let (lhs, rhs) = (l.type_name(), r.type_name());
+    if loose && matches!(op, CmpOp::Eq | CmpOp::Ne) && lhs != rhs && coerce::compare_numbers(&l, &r).is_none() {
+        context.warn(TalkEvalWarningKind::LooseEquality { lhs, rhs });
+    }
+    Ok(TalkValue::Bool(result))
+}
+
+#[inline(never)]
+fn eval_in<'world>(needle: &Expression, haystack: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let needle = needle.eval(context)?.into_owned();
+    let found = match (&*haystack.eval(context)?, &needle) {
+        (TalkValue::List(l), _) => l.contains(&needle),
+        // Proxied fields are only listed by `keys`, so the local map is checked first.
+        (TalkValue::Object(o), TalkValue::String(key)) => o.contains(key) || o.keys().contains(key),
+        (TalkValue::String(s), TalkValue::String(sub)) => s.contains(sub.as_str()),
+        (TalkValue::Object(_), other) | (TalkValue::String(_), other) =>
+            return Err(TalkEvalErrorKind::TypeMismatch { expected: "string", got: other.type_name() }.into()),
+        (other, _) => return Err(TalkEvalErrorKind::TypeMismatch { expected: "list, object or string", got: other.type_name() }.into()),
+    };
+    Ok(TalkValue::Bool(found))
+}
+
+#[inline(never)]
+fn eval_neg<'world>(operand: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    match &*operand.eval(context)? {
+        TalkValue::Int(i) => Ok(TalkValue::Int(i.checked_neg()
+            .ok_or_else(|| TalkEvalErrorKind::Overflow(format!("-({})", i)))?)),
+        TalkValue::Float(x) => Ok(TalkValue::Float(-x)),
+        other => Err(TalkEvalError::new(&format!("cannot negate {}", other.type_name()))),
+    }
+}
+
+#[inline(never)]
+fn eval_match<'ctx, 'world>(scrutinee: &Expression, arms: &[(Expression, Expression)], default: Option<&Expression>, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    let value = scrutinee.eval(context)?.into_owned();
+    let mut chosen = default;
+    for (pattern, body) in arms {
+        if *pattern.eval(context)? == value {
+            chosen = Some(body);
+            break;
+        }
+    }
+    match chosen {
+        Some(body) => body.eval(context),
+        None => Ok(Cow::Owned(TalkValue::Null)),
+    }
+}
+
+#[inline(never)]
+fn eval_while<'world>(cond: &Expression, body: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let max = context.max_iterations();
+    let mut last = TalkValue::Null;
+    let mut iterations = 0;
+    while coerce::to_bool(&*cond.eval(context)?) {
+        if iterations == max {
+            return Err(TalkEvalError::new(&format!("Loop exceeded {} iterations", max)));
+        }
+        iterations += 1;
+        context.take_step()?;
+        last = body.eval(context)?.into_owned();
+    }
+    Ok(last)
+}
+
+/// Runs `statements` in a frame of their own, popped whether they succeed or not. The
+/// result is owned, since it may borrow from the frame.
+#[inline(never)]
+fn eval_block<'world>(statements: &[Expression], context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let (last, rest) = match statements.split_last() {
+        Some(split) => split,
+        None => return Ok(TalkValue::Null),
+    };
+    context.scopes.push();
+    let mut run = || -> Result<TalkValue<'world>, TalkEvalError> {
+        for statement in rest {
+            statement.eval(context)?;
+        }
+        Ok(last.eval(context)?.into_owned())
+    };
+    let result = run();
+    context.scopes.pop();
+    result
+}
+
+#[inline(never)]
+fn eval_for_in<'world>(var: &str, iterable: &Expression, body: &Expression, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let items = match iterable.eval(context)?.into_owned() {
+        TalkValue::List(l) => l,
+        other => return Err(other.mismatch("list")),
+    };
+
+    context.scopes.push();
+    let run = || -> Result<TalkValue<'world>, TalkEvalError> {
+        let mut last = TalkValue::Null;
+        for item in items {
+            context.take_step()?;
+            context.scopes.bind(var, item);
+            last = body.eval(context)?.into_owned();
+        }
+        Ok(last)
+    };
+    let result = run();
+    context.scopes.pop();
+    result
+}
+
+#[inline(never)]
+fn eval_range<'world>(start: &Expression, end: &Expression, inclusive: bool, context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let start = match *start.eval(context)? {
+        TalkValue::Int(i) => i,
+        ref other => return Err(other.mismatch("int")),
+    };
+    let end = match *end.eval(context)? {
+        TalkValue::Int(i) => i,
+        ref other => return Err(other.mismatch("int")),
+    };
+    let len = (end as i128 - start as i128 + inclusive as i128).max(0);
+    let max = context.max_iterations();
+    if len > max as i128 {
+        let op = if inclusive { "..=" } else { ".." };
+        return Err(TalkEvalError::new(&format!("Range {}{}{} exceeds {} elements", start, op, end, max)));
+    }
+    Ok(TalkValue::List((0..len as i64).map(|i| TalkValue::Int(start + i)).collect()))
+}
+
+#[inline(never)]
+fn eval_list<'world>(items: &[Expression], context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let mut values = Vec::with_capacity(items.len());
+    for item in items {
+        values.push(item.eval(context)?.into_owned());
+    }
+    Ok(TalkValue::List(values))
+}
+
+#[inline(never)]
+fn eval_object<'world>(entries: &[(String, Expression)], context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let mut object = TalkObject::new();
+    for (key, value) in entries {
+        let value = value.eval(context)?.into_owned();
+        object.set(key, value)?;
+    }
+    Ok(TalkValue::Object(Box::new(object)))
+}
+
+#[inline(never)]
+fn eval_call<'world>(name: &str, args: &[Expression], context: &mut TalkObject<'world>) -> Result<TalkValue<'world>, TalkEvalError> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        values.push(arg.eval(context)?.into_owned());
+    }
+    match context.functions.get_mut(name) {
+        Some(f) => f(values),
+        None => match builtins::lookup(name) {
+            Some(f) => f(values),
+            None => Err(TalkEvalErrorKind::UndefinedFunction(name.to_string()).into()),
+        },
+    }
+}
+
+#[inline(never)]
+fn eval_assign<'ctx, 'world>(target: &Expression, value: &Expression, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    let val = value.eval(context)?.into_owned();
+    let (object, name) = match target {
+        Expression::Identifier(name) if context.scopes.contains(name) => {
+            let bound = context.scopes.get_mut(name).unwrap();
+            *bound = val;
+            return Ok(Cow::Borrowed(bound));
+        }
+        Expression::Identifier(name) => (context, name),
+        Expression::Field { base, name } => {
+            target.check_depth(context)?;
+            (base.eval_object(context)?, name)
+        }
+        other => return Err(TalkEvalError::new(&format!("cannot assign to {:?}", other))),
+    };
+    object.set(name, val)?;
+    // Read back through the object, so a proxy gets the final say on what was stored.
+    Ok(object.lookup_or_null(name))
+}
+
+fn eval_let<'ctx, 'world>(name: &str, value: &Expression, context: &'ctx mut TalkObject<'world>) -> EvalResult<'ctx, 'world> {
+    let val = value.eval(context)?.into_owned();
+    if context.scopes.depth() == 0 {
+        return Err(TalkEvalError::new(&format!("let {} outside of any scope", name)));
+    }
+    context.scopes.bind(name, val);
+    Ok(Cow::Borrowed(context.scopes.get_mut(name).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::eval::{Expression, TalkObject, Eval, TalkObjectProxy, TalkValue, TalkEvalError, TalkEvalErrorKind, Literal, BinOp, CmpOp, eval_str, MAX_RESOLVE_DEPTH, DEFAULT_MAX_DEPTH, DEFAULT_MAX_EVAL_DEPTH, ReadOnlyProxy, Span, TalkEvalWarningKind};
+    use crate::parser::parse;
+
+    struct DummyProxy<'world> {
+        value: TalkValue<'world>,
+        value2: TalkValue<'world>,
+    }
+
+    impl<'world> DummyProxy<'world> {
+        pub fn new() -> Self {
+            Self { value: TalkValue::Int(42), value2: TalkValue::Int(43) }
+        }
+    }
+
+    impl<'world> TalkObjectProxy<'world> for DummyProxy<'world> {
+        fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
+            println!("get()");
+            if name == "42" {
+                Some(&mut self.value)
+            } else {
+                Some(&mut self.value2)
+            }
+        }
+
+        fn set(&mut self, _name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+            println!("set()");
+            self.value = val;
+            Ok(())
+        }
+    }
+
+    struct StatsProxy<'world> {
+        hp: TalkValue<'world>,
+        name: TalkValue<'world>,
+    }
+
+    impl<'world> TalkObjectProxy<'world> for StatsProxy<'world> {
+        fn get(&mut self, name: &str) -> Option<&mut TalkValue<'world>> {
+            match name {
+                "hp" => Some(&mut self.hp),
+                "name" => Some(&mut self.name),
+                _ => None,
+            }
+        }
+
+        fn set(&mut self, name: &str, val: TalkValue<'world>) -> Result<(), TalkEvalError> {
+            if let Some(v) = self.get(name) {
+                *v = val;
+            }
+            Ok(())
+        }
+
+        fn keys(&self) -> Vec<String> { vec!["hp".to_string(), "name".to_string()] }
+    }
+
+    #[test]
+    fn test_bool() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let expr = Expression::Dummy;
+
+        let mut dummy = DummyProxy::new();
+        context.push_proxy(&mut dummy);
+
+        // The proxy resolves "43" to its second value, and eval borrows it from the proxy.
+        let a = expr.eval(&mut context)?;
+        assert!(matches!(a, Cow::Borrowed(TalkValue::Int(43))));
+        assert_eq!(context.get("43"), Some(&mut TalkValue::Int(43)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_float() -> Result<(), TalkEvalError> {
+        assert!(TalkValue::new_float(0.5).as_bool());
+        assert!(!TalkValue::new_float(0.0).as_bool());
+        assert!(!TalkValue::new_float(-0.0).as_bool());
+        assert!(!TalkValue::new_float(f64::NAN).as_bool());
+
+        assert_eq!(TalkValue::new_float(-2.9).into_int()?, -2);
+        assert!(TalkValue::new_float(f64::NAN).into_int().is_err());
+        assert!(TalkValue::new_float(f64::INFINITY).into_int().is_err());
+
+        let nan = TalkValue::new_float(f64::NAN).into_float()?;
+        assert!(nan.is_nan());
+        assert_ne!(nan, nan);
+
+        // 2^53 + 1 is not representable as f64 and rounds down.
+        let big = TalkValue::new_int(9_007_199_254_740_993).into_float()?;
+        assert_eq!(big, 9_007_199_254_740_992.0);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tagged-serde"))]
+    #[test]
+    fn test_float_serde() {
+        let values: Vec<TalkValue> = serde_json::from_str("[1, 1.0, -0.0, 2.5]").unwrap();
+        assert!(matches!(values[0], TalkValue::Int(1)));
+        assert!(matches!(values[1], TalkValue::Float(x) if x == 1.0));
+        assert!(matches!(values[2], TalkValue::Float(x) if x == 0.0 && x.is_sign_negative()));
+        assert!(matches!(values[3], TalkValue::Float(x) if x == 2.5));
+
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(json, "[1,1.0,-0.0,2.5]");
+    }
+
+    #[test]
+    fn test_null() {
+        let null = TalkValue::Null;
+        assert!(null.is_null());
+        assert!(!null.as_bool());
+        assert!(!TalkValue::new_obj().is_null());
+        assert_eq!(format!("{}", null), "null");
+        assert_eq!(format!("{:?}", null), "Null");
+
+        let err = TalkValue::Null.into_int().unwrap_err();
+        assert_eq!(err.text(), "expected int, got null");
+
+        #[cfg(not(feature = "tagged-serde"))]
+        {
+            let values: Vec<TalkValue> = serde_json::from_str("[null, {}]").unwrap();
+            assert!(values[0].is_null());
+            assert!(matches!(values[1], TalkValue::Object(_)));
+            assert_eq!(serde_json::to_string(&values).unwrap(), "[null,{}]");
+        }
+    }
+
+    #[test]
+    fn test_list() -> Result<(), TalkEvalError> {
+        let mut list = TalkValue::new_list();
+        assert!(!list.as_bool());
+        assert!(list.set_index(0, TalkValue::new_int(1)).is_err());
+
+        list = TalkValue::List(vec![TalkValue::new_int(1), TalkValue::new_str("b"), TalkValue::Bool(true)]);
+        assert!(list.as_bool());
+        assert_eq!(format!("{}", list), "[1, b, true]");
+
+        list.set_index(1, TalkValue::new_int(2))?;
+        assert!(matches!(list.get_index(1), Some(TalkValue::Int(2))));
+        assert!(list.get_index(3).is_none());
+
+        let err = list.set_index(3, TalkValue::Null).unwrap_err();
+        assert_eq!(err.kind, TalkEvalErrorKind::IndexOutOfBounds(3));
+
+        let err = list.as_object().unwrap_err();
+        assert_eq!(err.text(), "expected object, got list");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eq() {
+        let samples = [
+            TalkValue::new_int(1),
+            TalkValue::new_float(1.0),
+            TalkValue::new_str("1"),
+            TalkValue::Bool(true),
+            TalkValue::Null,
+            TalkValue::List(vec![TalkValue::new_int(1)]),
+            TalkValue::new_obj(),
+        ];
+        for (i, a) in samples.iter().enumerate() {
+            for (j, b) in samples.iter().enumerate() {
+                assert_eq!(a == b, i == j, "{:?} vs {:?}", a, b);
+            }
+        }
+
+        assert_ne!(TalkValue::new_int(1), TalkValue::new_int(2));
+        assert_ne!(TalkValue::new_str("a"), TalkValue::new_str("b"));
+        assert_ne!(TalkValue::Bool(true), TalkValue::Bool(false));
+        assert_ne!(TalkValue::new_float(f64::NAN), TalkValue::new_float(f64::NAN));
+        assert_ne!(TalkValue::List(vec![]), TalkValue::List(vec![TalkValue::Null]));
+    }
+
+    #[test]
+    fn test_eq_nested_objects() {
+        let make = |hp: i64| {
+            let mut inner = TalkObject::new();
+            inner.set("hp", TalkValue::new_int(hp)).unwrap();
+            let mut outer = TalkObject::new();
+            outer.set("player", TalkValue::Object(Box::new(inner))).unwrap();
+            TalkValue::Object(Box::new(outer))
+        };
+        assert_eq!(make(10), make(10));
+        assert_ne!(make(10), make(11));
+
+        // The proxy does not take part in the comparison.
+        let mut dummy = DummyProxy::new();
+        let mut proxied = TalkObject::new();
+        proxied.push_proxy(&mut dummy);
+        assert_eq!(TalkObject::new(), proxied);
+    }
+
+    #[test]
+    fn test_ord() {
+        assert!(TalkValue::new_int(2) > TalkValue::new_int(1));
+        assert!(TalkValue::new_float(-0.5) < TalkValue::new_float(0.0));
+        assert!(TalkValue::new_str("apple") < TalkValue::new_str("banana"));
+        assert!(TalkValue::Bool(false) < TalkValue::Bool(true));
+        assert!(TalkValue::new_int(1) <= TalkValue::new_int(1));
+
+        assert_eq!(TalkValue::new_obj().partial_cmp(&TalkValue::new_obj()), None);
+        assert_eq!(TalkValue::new_obj().partial_cmp(&TalkValue::new_int(1)), None);
+        assert_eq!(TalkValue::new_int(1).partial_cmp(&TalkValue::Bool(true)), None);
+        assert_eq!(TalkValue::new_float(f64::NAN).partial_cmp(&TalkValue::new_float(1.0)), None);
+
+        let err = TalkValue::new_str("a").try_cmp(&TalkValue::new_int(1)).unwrap_err();
+        assert_eq!(err.text(), "cannot order string against int");
+    }
+
+    #[test]
+    fn test_strict_accessors() {
+        let i = TalkValue::new_int(7);
+        assert_eq!(i.as_int(), Some(7));
+        assert_eq!(i.as_str(), None);
+        assert_eq!(i.as_bool_opt(), None);
+
+        let s = TalkValue::new_str("7");
+        assert_eq!(s.as_int(), None);
+        assert_eq!(s.as_str(), Some("7"));
+        assert_eq!(s.as_bool_opt(), None);
+
+        let b = TalkValue::Bool(false);
+        assert_eq!(b.as_int(), None);
+        assert_eq!(b.as_bool_opt(), Some(false));
+
+        assert_eq!(TalkValue::new_float(7.0).as_int(), None);
+        assert_eq!(TalkValue::Null.as_bool_opt(), None);
+        // The coercing accessor is unchanged.
+        assert!(i.as_bool());
+    }
+
+    #[test]
+    fn test_from() {
+        let i: TalkValue = 42.into();
+        assert_eq!(i, TalkValue::new_int(42));
+        assert_eq!(TalkValue::from(0.5), TalkValue::new_float(0.5));
+        assert_eq!(TalkValue::from(true), TalkValue::Bool(true));
+        assert_eq!(TalkValue::from("hi"), TalkValue::new_str("hi"));
+        assert_eq!(TalkValue::from("hi".to_string()), TalkValue::new_str("hi"));
+
+        let mut obj = TalkObject::new();
+        obj.set("name", "Bob".into()).unwrap();
+        assert_eq!(obj.get("name").unwrap().as_str(), Some("Bob"));
+    }
+
+    #[test]
+    fn test_display_debug() {
+        assert_eq!(format!("{}", TalkValue::new_str("42")), "42");
+        assert_eq!(format!("{}", TalkValue::new_int(42)), "42");
+        assert_eq!(format!("{:?}", TalkValue::new_str("42")), "String(\"42\")");
+        assert_eq!(format!("{:?}", TalkValue::new_int(42)), "Int(42)");
+        assert_eq!(format!("{:?}", TalkValue::Bool(true)), "Bool(true)");
+        assert_eq!(format!("{:?}", TalkValue::new_float(1.0)), "Float(1.0)");
+        assert_eq!(format!("{:?}", TalkValue::List(vec![1.into(), "a".into()])), "List([Int(1), String(\"a\")])");
+
+        let mut obj = TalkObject::new();
+        obj.set("b", 2.into()).unwrap();
+        obj.set("a", "x".into()).unwrap();
+        let obj = TalkValue::Object(Box::new(obj));
+        assert_eq!(format!("{}", obj), "{a: x, b: 2}");
+
+        let mut dummy = DummyProxy::new();
+        let mut proxied = TalkObject::new();
+        proxied.push_proxy(&mut dummy);
+        assert_eq!(format!("{:?}", TalkValue::Object(Box::new(proxied))), "Object({}, proxy: true)");
+    }
+
+    #[test]
+    fn test_pretty_debug() -> Result<(), TalkEvalError> {
+        let mut dummy = DummyProxy::new();
+        let mut obj = TalkObject::new();
+        eval_str("{ name = \"Bob\"; stats = { hp: 10, tags: [1, {}] }; empty = [] }", &mut obj)?;
+        obj.push_proxy(&mut dummy);
+        assert_eq!(format!("{:#?}", obj), r#"{
+    "empty": List([]),
+    "name": String("Bob"),
+    "stats": Object({
+        "hp": Int(10),
+        "tags": List([
+            Int(1),
+            Object({}, proxy: false),
+        ]),
+    }, proxy: false),
+}, proxy: true"#);
+        assert!(!format!("{:?}", obj).contains('\n'));
+        Ok(())
+    }
+
+    fn int(i: i64) -> Box<Expression> { Box::new(Expression::Literal(Literal::Int(i))) }
+
+    fn binary(op: BinOp, lhs: Box<Expression>, rhs: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Binary { op, lhs, rhs })
+    }
+
+    #[test]
+    fn test_binary() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+
+        // 2 + 3 * 4
+        let expr = binary(BinOp::Add, int(2), binary(BinOp::Mul, int(3), int(4)));
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(14));
+
+        let expr = binary(BinOp::Sub, int(7), binary(BinOp::Mod, int(7), int(4)));
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(4));
+        assert_eq!(*binary(BinOp::Div, int(7), int(2)).eval(&mut context)?, TalkValue::Int(3));
+
+        let err = binary(BinOp::Div, int(1), int(0)).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "Division by zero");
+        assert!(binary(BinOp::Mod, int(1), int(0)).eval(&mut context).is_err());
+
+        let text = Box::new(Expression::Literal(Literal::String("a".to_string())));
+        let err = binary(BinOp::Mul, int(1), text).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "Mul expects int operands, got string");
+
+        Ok(())
+    }
+
+    fn lit(l: Literal) -> Box<Expression> { Box::new(Expression::Literal(l)) }
+
+    fn compare(op: CmpOp, lhs: Box<Expression>, rhs: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Compare { op, lhs, rhs })
+    }
+
+    #[test]
+    fn test_compare() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let a = || lit(Literal::String("a".to_string()));
+
+        assert_eq!(*compare(CmpOp::Lt, int(1), int(2)).eval(&mut context)?, TalkValue::Bool(true));
+        assert_eq!(*compare(CmpOp::Ge, int(1), int(2)).eval(&mut context)?, TalkValue::Bool(false));
+        assert_eq!(*compare(CmpOp::Eq, a(), a()).eval(&mut context)?, TalkValue::Bool(true));
+        let not_equal = compare(CmpOp::Ne, lit(Literal::Bool(true)), lit(Literal::Bool(false)));
+        assert_eq!(*not_equal.eval(&mut context)?, TalkValue::Bool(true));
+
+        // Equality is total across types, ordering is not.
+        assert_eq!(*compare(CmpOp::Eq, int(1), a()).eval(&mut context)?, TalkValue::Bool(false));
+        assert_eq!(*compare(CmpOp::Ne, int(1), a()).eval(&mut context)?, TalkValue::Bool(true));
+        let err = compare(CmpOp::Lt, a(), int(1)).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "cannot order string against int");
+
+        // Int and Float compare by value.
+        assert_eq!(eval_str("1 < 1.5", &mut context)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("2.0 == 2", &mut context)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("2.5 != 2", &mut context)?, TalkValue::Bool(true));
+        context.set("big", TalkValue::new_int(9_007_199_254_740_993))?;
+        assert_eq!(eval_str("big > 9007199254740992.0", &mut context)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("big == 9007199254740992.0", &mut context)?, TalkValue::Bool(false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_and_or() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let x = || lit(Literal::String("x".to_string()));
+        let failing = || binary(BinOp::Div, int(1), int(0));
+
+        let or = Expression::Or { lhs: int(0), rhs: x() };
+        assert_eq!(*or.eval(&mut context)?, TalkValue::new_str("x"));
+        let or = Expression::Or { lhs: int(5), rhs: failing() };
+        assert_eq!(*or.eval(&mut context)?, TalkValue::Int(5));
+
+        let and = Expression::And { lhs: int(0), rhs: failing() };
+        assert_eq!(*and.eval(&mut context)?, TalkValue::Int(0));
+        let and = Expression::And { lhs: int(1), rhs: x() };
+        assert_eq!(*and.eval(&mut context)?, TalkValue::new_str("x"));
+
+        // The rhs does run when it decides the result.
+        assert!(Expression::And { lhs: int(1), rhs: failing() }.eval(&mut context).is_err());
+        assert!(Expression::Or { lhs: int(0), rhs: failing() }.eval(&mut context).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_neg() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let not = |operand| Box::new(Expression::Not { operand });
+        let neg = |operand| Box::new(Expression::Neg { operand });
+
+        // !false == true
+        let expr = compare(CmpOp::Eq, not(lit(Literal::Bool(false))), lit(Literal::Bool(true)));
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Bool(true));
+        assert_eq!(*not(not(int(3))).eval(&mut context)?, TalkValue::Bool(true));
+
+        // -(-5) == 5
+        let expr = compare(CmpOp::Eq, neg(neg(int(5))), int(5));
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Bool(true));
+        assert_eq!(*neg(lit(Literal::Float(1.5))).eval(&mut context)?, TalkValue::Float(-1.5));
+
+        let err = neg(lit(Literal::String("a".to_string()))).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "cannot negate string");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let conditional = |cond, then_branch, else_branch| Expression::Conditional { cond, then_branch, else_branch };
+        let failing = || binary(BinOp::Div, int(1), int(0));
+
+        let expr = conditional(lit(Literal::Bool(true)), int(1), failing());
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(1));
+        let expr = conditional(lit(Literal::String(String::new())), failing(), int(2));
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let text = |s: &str| lit(Literal::String(s.to_string()));
+        let mut add = |lhs, rhs| binary(BinOp::Add, lhs, rhs).eval(&mut context).map(Cow::into_owned);
+
+        assert_eq!(add(int(1), int(2))?, TalkValue::Int(3));
+        assert_eq!(add(text("a"), text("b"))?, TalkValue::new_str("ab"));
+        assert_eq!(add(text("HP: "), int(100))?, TalkValue::new_str("HP: 100"));
+        assert_eq!(add(int(100), text(" HP"))?, TalkValue::new_str("100 HP"));
+        assert_eq!(add(text("x"), lit(Literal::Float(0.5)))?, TalkValue::new_str("x0.5"));
+        assert_eq!(add(text("ok: "), lit(Literal::Bool(true)))?, TalkValue::new_str("ok: true"));
+
+        assert_eq!(add(text("a"), lit(Literal::Null)).unwrap_err().text(), "cannot concatenate string and null");
+        assert_eq!(add(int(1), lit(Literal::Bool(true))).unwrap_err().text(), "Add expects int operands, got bool");
+
+        // Strings only concatenate; other operators still need Int.
+        let err = binary(BinOp::Sub, text("a"), text("b")).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "Sub expects int operands, got string");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assign() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let assign = |target: &str, value| Expression::Assign { target: ident(target), value };
+
+        let stored = assign("score", binary(BinOp::Add, int(4), int(6))).eval(&mut context)?;
+        assert_eq!(*stored, TalkValue::Int(10));
+        assert_eq!(*context.get("score").unwrap(), TalkValue::Int(10));
+
+        assign("score", int(11)).eval(&mut context)?;
+        assert_eq!(*context.get("score").unwrap(), TalkValue::Int(11));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifier() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("hp", TalkValue::new_int(7))?;
+
+        assert_eq!(*Expression::Identifier("hp".to_string()).eval(&mut context)?, TalkValue::Int(7));
+        assert_eq!(*Expression::Identifier("ghost".to_string()).eval(&mut context)?, TalkValue::Null);
+        assert!(!context.map.contains_key("ghost"));
+
+        // Reads borrow from the context; computed values are owned.
+        assert!(matches!(ident("hp").eval(&mut context)?, Cow::Borrowed(_)));
+        assert!(matches!(binary(BinOp::Add, ident("hp"), int(1)).eval(&mut context)?, Cow::Owned(_)));
+
+        let mut dummy = DummyProxy::new();
+        context.push_proxy(&mut dummy);
+        assert_eq!(*Expression::Identifier("42".to_string()).eval(&mut context)?, TalkValue::Int(42));
+
+        Ok(())
+    }
+
+    fn ident(name: &str) -> Box<Expression> { Box::new(Expression::Identifier(name.to_string())) }
+
+    fn field(base: Box<Expression>, name: &str) -> Box<Expression> {
+        Box::new(Expression::Field { base, name: name.to_string() })
+    }
+
+    #[test]
+    fn test_field() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let mut b = TalkObject::new();
+        b.set("c", TalkValue::new_int(3))?;
+        let mut a = TalkObject::new();
+        a.set("b", TalkValue::Object(Box::new(b)))?;
+        a.set("n", TalkValue::new_int(1))?;
+        context.set("a", TalkValue::Object(Box::new(a)))?;
+
+        assert_eq!(*field(ident("a"), "n").eval(&mut context)?, TalkValue::Int(1));
+        assert_eq!(*field(field(ident("a"), "b"), "c").eval(&mut context)?, TalkValue::Int(3));
+        assert_eq!(*field(field(ident("a"), "b"), "missing").eval(&mut context)?, TalkValue::Null);
+
+        let assign = |target, value| Expression::Assign { target, value };
+        assign(field(ident("a"), "n"), int(2)).eval(&mut context)?;
+        let stored = assign(field(field(ident("a"), "b"), "d"), int(4)).eval(&mut context)?;
+        assert_eq!(*stored, TalkValue::Int(4));
+        assert_eq!(*field(ident("a"), "n").eval(&mut context)?, TalkValue::Int(2));
+        assert_eq!(*field(field(ident("a"), "b"), "d").eval(&mut context)?, TalkValue::Int(4));
+
+        let err = field(field(ident("a"), "n"), "x").eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "a.n is not an object, got int");
+        let err = assign(field(field(ident("a"), "n"), "x"), int(0)).eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "a.n is not an object, got int");
+        let err = field(field(ident("a"), "ghost"), "x").eval(&mut context).unwrap_err();
+        assert_eq!(err.text(), "a.ghost is not an object, got null");
+        assert!(!context.get("a").unwrap().as_object()?.map.contains_key("ghost"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_str() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("1 + 2", &mut ctx)?, TalkValue::Int(3));
+
+        eval_str("hp = 10", &mut ctx)?;
+        assert_eq!(eval_str("hp > 5 ? \"healthy\" : \"hurt\"", &mut ctx)?, TalkValue::new_str("healthy"));
+
+        assert!(eval_str("1 +", &mut ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_trait() {
+        fn fails() -> Result<(), Box<dyn std::error::Error>> {
+            eval_str("1 / 0", &mut TalkObject::new())?;
+            Ok(())
+        }
+        assert_eq!(fails().unwrap_err().to_string(), "error at 1:1: Division by zero");
+
+        let err = eval_str("1 +", &mut TalkObject::new()).unwrap_err();
+        assert_eq!(err.to_string(), "error at 1:4: Parse error at end of input: expected unary");
+    }
+
+    #[test]
+    fn test_runtime_error_spans() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        ctx.set("name", "Bob".into())?;
+        let span = |src: &str, ctx: &mut TalkObject| eval_str(src, ctx).unwrap_err().span.map(|s| (s.start, s.end, s.line, s.col));
+
+        let err = eval_str("1 + (2 * \"a\" - 3)", &mut ctx).unwrap_err();
+        assert_eq!(err.span, Some(Span { start: 5, end: 12, line: 1, col: 6 }));
+        assert_eq!(err.to_string(), "error at 1:6: Mul expects int operands, got string");
+        assert_eq!(span("{\n  x = 1;\n  x + -name\n}", &mut ctx), Some((17, 22, 3, 7)));
+        assert_eq!(span("[1, 2][5] + 1", &mut ctx), Some((0, 9, 1, 1)));
+        assert_eq!(span("-nope(1) * 2", &mut ctx), Some((1, 8, 1, 2)));
+        assert_eq!(span("name < 1 || true", &mut ctx), Some((0, 8, 1, 1)));
+
+        // Hand-built trees and plain `parse` carry no spans.
+        assert_eq!(parse("1 / 0")?.eval(&mut ctx).unwrap_err().span, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() {
+        let src = "{\n  x = 1;\n  (2 * \"a\") + x\n}";
+        let err = eval_str(src, &mut TalkObject::new()).unwrap_err();
+        assert_eq!(err.render(src), "error at 3:4: Mul expects int operands, got string\n  |\n3 |   (2 * \"a\") + x\n  |    ^^^^^^^");
+
+        let src = "\t[1,\n 2][9]";
+        let err = eval_str(src, &mut TalkObject::new()).unwrap_err();
+        assert_eq!(err.render(src), "error at 1:2: Index 9 out of bounds\n  |\n1 | \t[1,\n  | \t^^^");
+
+        let src = "1 +";
+        assert_eq!(parse(src).unwrap_err().render(src), "error at 1:4: Parse error at end of input: expected unary\n  |\n1 | 1 +\n  |    ^");
+        assert_eq!(TalkEvalError::new("oops").render(src), "oops");
+        assert_eq!(TalkEvalError::new("oops").with_span(Span { start: 99, end: 99, line: 9, col: 9 }).render(src), "error at 9:9: oops");
+    }
+
+    #[test]
+    fn test_warnings() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::builder().collect_warnings(true).loose_equality(true).build();
+        context.set("hp", TalkValue::new_int(5))?;
+        assert_eq!(eval_str("{ x = missing; hp + 1 }", &mut context)?, TalkValue::Int(6));
+        assert_eq!(eval_str("(\"5\" == hp) && typeof(ghost) == \"null\" && 1 == 1.0", &mut context)?, TalkValue::Bool(true));
+
+        let warnings = context.take_warnings();
+        let text: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(text, vec![
+            "Read undefined variable missing as null",
+            "warning at 1:2: Loosely compared string with int",
+            "warning at 1:16: Read undefined variable ghost as null",
+        ]);
+        assert_eq!(warnings[0].kind, TalkEvalWarningKind::UndefinedVariable("missing".to_string()));
+        assert!(context.take_warnings().is_empty());
+
+        // Inside an operator, a warning gets its span.
+        eval_str("hp + (nope ?? 1)", &mut context)?;
+        assert_eq!(context.take_warnings()[0].span, Some(Span { start: 0, end: 16, line: 1, col: 1 }));
+
+        // Off by default.
+        let mut quiet = TalkObject::new();
+        assert_eq!(eval_str("missing", &mut quiet)?, TalkValue::Null);
+        assert!(quiet.take_warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_all() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("gold", TalkValue::new_int(30))?;
+        let results = context.eval_all(&["gold >= 20", "gold >", "seen = true", "gold / 0", "seen && gold < 50"]);
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().ok(), Some(&TalkValue::Bool(true)));
+        assert!(matches!(results[1].as_ref().unwrap_err().kind, TalkEvalErrorKind::ParseError(_)));
+        assert_eq!(results[2].as_ref().ok(), Some(&TalkValue::Bool(true)));
+        assert_eq!(results[3].as_ref().unwrap_err().kind, TalkEvalErrorKind::DivisionByZero);
+        assert_eq!(results[4].as_ref().ok(), Some(&TalkValue::Bool(true)));
+        assert!(context.eval_all(&[]).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("1 % 0", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::DivisionByZero);
+        assert!(matches!(eval_str("1 +", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::ParseError(_)));
+
+        let err = TalkValue::new_str("42").into_int().unwrap_err();
+        assert_eq!(err.kind, TalkEvalErrorKind::TypeMismatch { expected: "int", got: "string" });
+        assert_eq!(err.text(), "expected int, got string");
+
+        let err = TalkEvalError::new("custom");
+        assert_eq!(err.kind, TalkEvalErrorKind::Message("custom".to_string()));
+    }
+
+    #[test]
+    fn test_index_operator() {
+        let mut object = TalkObject::new();
+        object["hp"] = TalkValue::new_int(10);
+        object["name"] = "Bob".into();
+        assert_eq!(object["hp"], TalkValue::Int(10));
+        assert_eq!(object["name"].as_str(), Some("Bob"));
+
+        if let TalkValue::Int(hp) = &mut object["hp"] {
+            *hp -= 3;
+        }
+        assert_eq!(object["hp"], TalkValue::Int(7));
+        let ghost = &mut object["ghost"];
+        assert_eq!(*ghost, TalkValue::Null);
+        assert_eq!(object.local_len(), 3);
+
+        // Proxies are bypassed in both directions.
+        let mut stats = StatsProxy { hp: TalkValue::new_int(1), name: TalkValue::Null };
+        let mut proxied = TalkObject::new();
+        proxied.push_proxy(&mut stats);
+        proxied["hp"] = TalkValue::new_int(2);
+        assert_eq!(proxied["hp"], TalkValue::Int(2));
+        assert_eq!(proxied.get("hp").cloned(), Some(TalkValue::Int(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "no field \"missing\" in the TalkObject map")]
+    fn test_index_operator_missing() {
+        let object = TalkObject::new();
+        let _ = &object["missing"];
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "sync"), allow(clippy::arc_with_non_send_sync))]
+    fn test_into_iterator() {
+        let mut stats = StatsProxy { hp: TalkValue::new_int(1), name: TalkValue::Null };
+        let mut object = TalkObject::new();
+        object.push_proxy(&mut stats);
+        object.set("gold", TalkValue::new_int(5)).unwrap();
+        object.set("level", TalkValue::new_int(2)).unwrap();
+        let mut template = TalkObject::new();
+        template.set("title", TalkValue::new_str("Sir")).unwrap();
+        object.set_base(std::sync::Arc::new(template));
+
+        for (_, value) in &mut object {
+            if let TalkValue::Int(i) = value {
+                *i *= 10;
+            }
+        }
+        let mut pairs: Vec<(String, TalkValue)> = (&object).into_iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(pairs, vec![
+            ("gold".to_string(), TalkValue::Int(50)),
+            ("level".to_string(), TalkValue::Int(20)),
+        ]);
+    }
+
+    #[test]
+    fn test_from_iterator() -> Result<(), TalkEvalError> {
+        let pairs = vec![
+            ("hp".into(), 100i64.into()),
+            ("name".into(), "Bob".into()),
+            ("hp".into(), 90i64.into()),
+        ];
+        let mut object: TalkObject = pairs.into_iter().collect();
+        assert_eq!(object.local_len(), 2);
+        assert_eq!(object["hp"], TalkValue::Int(90));
+        assert_eq!(eval_str("name + \": \" + hp", &mut object)?.as_str(), Some("Bob: 90"));
+        assert_eq!(object.resolve()?, object.clone());
+        Ok(())
+    }
+
+    fn template() -> TalkObject<'static> {
+        let mut object = TalkObject::new();
+        object.set_path("name", "Goblin".into()).unwrap();
+        object.set_path("stats.hp", TalkValue::new_int(10)).unwrap();
+        object.set_path("stats.mp", TalkValue::new_int(2)).unwrap();
+        object.set_path("loot", TalkValue::List(vec![TalkValue::new_str("club")])).unwrap();
+        object
+    }
+
+    #[test]
+    fn test_merge() -> Result<(), TalkEvalError> {
+        let mut overrides = TalkObject::new();
+        eval_str("{ name = \"Boss\"; stats = { hp: 50 }; loot = []; title = \"Big\" }", &mut overrides)?;
+
+        let mut object = template();
+        object.merge(overrides.clone());
+        assert_eq!(object.local_len(), 4);
+        assert_eq!(eval_str("name + \" the \" + title", &mut object)?.as_str(), Some("Boss the Big"));
+        assert_eq!(eval_str("stats", &mut object)?.to_string(), "{hp: 50}");
+        assert_eq!(eval_str("loot", &mut object)?, TalkValue::new_list());
+
+        let mut object = template();
+        object.merge_deep(overrides);
+        assert_eq!(eval_str("stats", &mut object)?.to_string(), "{hp: 50, mp: 2}");
+        assert_eq!(eval_str("name", &mut object)?.as_str(), Some("Boss"));
+        assert_eq!(eval_str("loot", &mut object)?, TalkValue::new_list());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_deep_nested() -> Result<(), TalkEvalError> {
+        let mut object = TalkObject::new();
+        eval_str("{ a = { b: { c: 1, d: 2 }, e: 3 }; f = { g: 4 } }", &mut object)?;
+        let mut overrides = TalkObject::new();
+        eval_str("{ a = { b: { c: 10, x: 0 } }; f = 5 }", &mut overrides)?;
+
+        object.merge_deep(overrides);
+        assert_eq!(eval_str("a.b.c + a.b.d + a.b.x + a.e + f", &mut object)?, TalkValue::Int(20));
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<(), TalkEvalError> {
+        let mut before = TalkObject::new();
+        eval_str("{ name = \"Bob\"; hp = 10; gold = 5; quest = { stage: 1, giver: \"Ann\" }; bag = { } }", &mut before)?;
+        let mut after = before.clone();
+        eval_str("{ hp = 7; xp = 100; quest.stage = 2; bag.gem = 1 }", &mut after)?;
+        after.map.remove("gold");
+
+        let mut changes = before.diff(&after);
+        assert_eq!(changes.to_string(), "{bag: {gem: 1}, gold: null, hp: 7, quest: {stage: 2}, xp: 100}");
+        assert_eq!(before.diff(&before).local_len(), 0);
+        assert_eq!(eval_str("quest.giver", &mut changes)?, TalkValue::Null);
+
+        before.merge_deep(changes);
+        before.map.remove("gold");
+        assert_eq!(before, after);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "sync"), allow(clippy::arc_with_non_send_sync))]
+    fn test_get_or() -> Result<(), TalkEvalError> {
+        let mut object = TalkObject::builder().auto_vivify(true).build();
+        eval_str("{ config = { volume: 7, muted: null }; title = \"Talk\" }", &mut object)?;
+        let before = object.clone();
+
+        assert_eq!(object.get_or("config.volume", TalkValue::new_int(5)), TalkValue::Int(7));
+        assert_eq!(object.get_or("config.muted", TalkValue::Bool(false)), TalkValue::Null);
+        assert_eq!(object.get_or("config.speed", TalkValue::new_int(1)), TalkValue::Int(1));
+        assert_eq!(object.get_or("audio.music.volume", TalkValue::new_int(3)), TalkValue::Int(3));
+        assert_eq!(object.get_or("title.length", TalkValue::Null), TalkValue::Null);
+        assert_eq!(object, before);
+
+        // Base fields are read where they are.
+        let mut instance = TalkObject::new();
+        instance.set_base(std::sync::Arc::new(before));
+        assert_eq!(instance.get_or("config.volume", TalkValue::new_int(5)), TalkValue::Int(7));
+        assert_eq!(instance.get_or("config.speed", TalkValue::new_int(1)), TalkValue::Int(1));
+        assert_eq!(instance.get_or("title", TalkValue::Null), TalkValue::new_str("Talk"));
+        assert_eq!(instance.local_len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_set() -> Result<(), TalkEvalError> {
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut stats = StatsProxy { hp: TalkValue::new_int(1), name: TalkValue::Null };
+        let mut context = TalkObject::new();
+        context.push_proxy(&mut stats);
+        let seen = log.clone();
+        context.on_set(move |name, value| seen.lock().unwrap().push(format!("{} = {}", name, value)));
+
+        context.set("gold", TalkValue::new_int(5))?;
+        eval_str("{ hp = hp + 9; player = { }; player.name = \"Bob\" }", &mut context)?;
+        context["gold"] = TalkValue::new_int(6);
+        assert_eq!(*log.lock().unwrap(), vec!["gold = 5", "hp = 10", "player = {}"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_freeze() -> Result<(), TalkEvalError> {
+        let mut template = TalkObject::builder().auto_vivify(true).build();
+        eval_str("{ name = \"Goblin\"; stats = { hp: 10 } }", &mut template)?;
+        let mut instance = template.clone();
+        template.freeze();
+        assert!(template.is_frozen());
+
+        assert_eq!(eval_str("hp = 1", &mut template).unwrap_err().kind, TalkEvalErrorKind::ReadOnly("hp".to_string()));
+        assert_eq!(template.set("name", "Orc".into()).unwrap_err().text(), "Field name is read-only");
+        assert_eq!(template.remove("name"), None);
+        assert_eq!(template.get("missing"), None);
+        assert_eq!(eval_str("name + \" \" + stats.hp", &mut template)?.as_str(), Some("Goblin 10"));
+
+        // A shallow freeze leaves nested objects writable.
+        assert_eq!(eval_str("stats.hp = 12", &mut template)?, TalkValue::Int(12));
+        assert!(!template.clone().is_frozen());
+        assert_eq!(eval_str("name = \"Orc\"", &mut instance)?.as_str(), Some("Orc"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_freeze_deep() -> Result<(), TalkEvalError> {
+        let mut template = TalkObject::new();
+        eval_str("{ stats = { hp: 10 }; party = [{ name: \"Ann\" }] }", &mut template)?;
+        template.freeze_deep();
+
+        assert_eq!(eval_str("stats.hp = 1", &mut template).unwrap_err().text(), "Field hp is read-only");
+        assert_eq!(template.set_path("stats.mp", TalkValue::new_int(1)).unwrap_err().text(), "Field mp is read-only");
+        assert_eq!(eval_str("party[0].name", &mut template)?.as_str(), Some("Ann"));
+        match template.get_path("party") {
+            Some(TalkValue::List(items)) => assert!(items[0].as_object()?.is_frozen()),
+            other => panic!("unexpected {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "sync"), allow(clippy::arc_with_non_send_sync))]
+    fn test_base() -> Result<(), TalkEvalError> {
+        use std::sync::Arc;
+
+        let mut template = TalkObject::new();
+        eval_str("{ name = \"Goblin\"; stats = { hp: 10, mp: 2 } }", &mut template)?;
+        let template = Arc::new(template);
+
+        let mut a = TalkObject::new();
+        let mut b = TalkObject::new();
+        a.set_base(template.clone());
+        b.set_base(template.clone());
+        assert_eq!(Arc::strong_count(&template), 3);
+
+        // Reads fall through without copying.
+        assert_eq!(eval_str("name", &mut a)?.as_str(), Some("Goblin"));
+        assert_eq!(eval_str("name", &mut b)?.as_str(), Some("Goblin"));
+        assert_eq!((a.local_len(), b.local_len()), (0, 0));
+
+        // Paths through a base field are read where they are, too.
+        assert_eq!(eval_str("stats.hp + stats[\"mp\"]", &mut a)?, TalkValue::new_int(12));
+        assert_eq!(eval_str("stats?.hp", &mut a)?, TalkValue::new_int(10));
+        assert_eq!(a.local_len(), 0);
+        assert!(a == b);
+
+        // Writes diverge, leaving the base and the other instance alone.
+        eval_str("{ name = \"Grak\"; stats.hp = 3 }", &mut a)?;
+        assert_eq!(eval_str("name + stats.hp + stats.mp", &mut a)?.as_str(), Some("Grak32"));
+        assert_eq!(eval_str("name + stats.hp", &mut b)?.as_str(), Some("Goblin10"));
+        assert_eq!(a.local_len(), 2);
+        assert_eq!(template.to_string(), "{name: Goblin, stats: {hp: 10, mp: 2}}");
+
+        assert_eq!(a.remove("name").unwrap().as_str(), Some("Grak"));
+        assert_eq!(eval_str("name", &mut a)?.as_str(), Some("Goblin"));
+        assert_eq!(a.resolve()?.to_string(), "{name: Goblin, stats: {hp: 3, mp: 2}}");
+        assert_eq!(a.keys(), vec!["name", "stats"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_vivify() {
+        let mut lenient = TalkObject::builder().auto_vivify(true).build();
+        assert_eq!(lenient.get("ghost").cloned(), Some(TalkValue::new_obj()));
+        assert!(lenient.map.contains_key("ghost"));
+
+        let mut strict = TalkObject::new();
+        assert!(strict.get("ghost").is_none());
+        assert!(!strict.map.contains_key("ghost"));
+        let mut strict = TalkObject::builder().auto_vivify(false).build();
+        assert!(strict.get("ghost").is_none());
+        assert!(!strict.map.contains_key("ghost"));
+        strict.set("hp", TalkValue::new_int(1)).unwrap();
+        assert_eq!(strict.get("hp").cloned(), Some(TalkValue::Int(1)));
+
+        // Proxies decide for themselves.
+        let mut dummy = DummyProxy::new();
+        strict.push_proxy(&mut dummy);
+        assert_eq!(strict.get("ghost").cloned(), Some(TalkValue::Int(43)));
+    }
+
+    #[test]
+    fn test_if() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let failing = || binary(BinOp::Div, int(1), int(0));
+        let truthy = || lit(Literal::Bool(true));
+        let falsy = || lit(Literal::Bool(false));
+
+        let expr = Expression::If { cond: truthy(), then_block: int(1), else_block: Some(failing()) };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(1));
+        let expr = Expression::If { cond: falsy(), then_block: failing(), else_block: Some(int(2)) };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(2));
+        let expr = Expression::If { cond: falsy(), then_block: failing(), else_block: None };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Null);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("mood", TalkValue::new_str("angry"))?;
+        context.set("calls", TalkValue::new_int(0))?;
+        let count = || Box::new(crate::parser::parse("calls = calls + 1").unwrap());
+        let s = |text: &str| lit(Literal::String(text.to_string()));
+        let dialogue = |default: Option<Box<Expression>>| Expression::Match {
+            scrutinee: ident("mood"),
+            arms: vec![
+                (*s("happy"), *count()),
+                (*s("angry"), *s("Go away")),
+                (*s("angry"), *count()),
+            ],
+            default,
+        };
+
+        assert_eq!(*dialogue(Some(count())).eval(&mut context)?, TalkValue::new_str("Go away"));
+        assert_eq!(*context.get("calls").unwrap(), TalkValue::Int(0));
+
+        context.set("mood", TalkValue::new_str("bored"))?;
+        assert_eq!(*dialogue(Some(s("..."))).eval(&mut context)?, TalkValue::new_str("..."));
+        assert_eq!(*dialogue(None).eval(&mut context)?, TalkValue::Null);
+        assert_eq!(*context.get("calls").unwrap(), TalkValue::Int(0));
+
+        // Patterns are compared strictly, and ones after the match are not evaluated.
+        let expr = Expression::Match {
+            scrutinee: int(1),
+            arms: vec![(*s("1"), *s("string")), (*binary(BinOp::Sub, int(2), int(1)), *s("int")), (*count(), *s("never"))],
+            default: None,
+        };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::new_str("int"));
+        assert_eq!(*context.get("calls").unwrap(), TalkValue::Int(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_while() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("i", TalkValue::new_int(0))?;
+        let increment = Box::new(Expression::Assign { target: ident("i"), value: binary(BinOp::Add, ident("i"), int(1)) });
+
+        let expr = Expression::While { cond: compare(CmpOp::Lt, ident("i"), int(5)), body: increment.clone() };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(5));
+        assert_eq!(*context.get("i").unwrap(), TalkValue::Int(5));
+
+        let expr = Expression::While { cond: lit(Literal::Bool(false)), body: increment.clone() };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Null);
+
+        context.set_max_iterations(10);
+        let expr = Expression::While { cond: lit(Literal::Bool(true)), body: increment };
+        assert_eq!(expr.eval(&mut context).unwrap_err().text(), "Loop exceeded 10 iterations");
+        assert_eq!(*context.get("i").unwrap(), TalkValue::Int(15));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let expr = Expression::Block(vec![
+            Expression::Assign { target: ident("x"), value: int(1) },
+            Expression::Assign { target: ident("y"), value: int(2) },
+            *binary(BinOp::Add, ident("x"), ident("y")),
+        ]);
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(3));
+        assert_eq!(*context.get("y").unwrap(), TalkValue::Int(2));
+
+        assert_eq!(*Expression::Block(vec![]).eval(&mut context)?, TalkValue::Null);
+        assert_eq!(eval_str("{ x = 10; { y = x * 2; y + 1 } }", &mut context)?, TalkValue::Int(21));
+        assert_eq!(eval_str("{ 1; missing(); 2 }", &mut context).unwrap_err().text(), "Undefined function missing");
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_scope() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("x", TalkValue::new_int(1))?;
+
+        // `let` shadows the field, and assignment then updates the local.
+        assert_eq!(eval_str("{ let x = 2; let y = x + 1; x = x * 10; x + y }", &mut context)?, TalkValue::new_int(23));
+        // Cleanup after the block ends: its frame is gone, along with its locals.
+        assert_eq!(context.scopes().depth(), 0);
+        assert_eq!(eval_str("x", &mut context)?, TalkValue::new_int(1));
+        assert!(!context.contains("y"));
+
+        // An inner block updates an outer binding, which outlives the inner `let` local.
+        // Plain assignment to an unbound name still writes the context, however deep.
+        assert_eq!(eval_str("{ let a = 1; { let b = 2; a = a + b; total = a }; a }", &mut context)?, TalkValue::new_int(3));
+        assert_eq!(eval_str("{ { let b = 2 }; b }", &mut context)?, TalkValue::Null);
+        assert_eq!(*context.get("total").unwrap(), TalkValue::new_int(3));
+        assert!(!context.contains("a"));
+        assert!(!context.contains("b"));
+
+        // Frames are popped on errors too.
+        assert_eq!(eval_str("{ let z = 1; { let w = z; w / 0 } }", &mut context).unwrap_err().text(), "Division by zero");
+        assert_eq!(context.scopes().depth(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_literal() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("x", TalkValue::new_int(2))?;
+        assert_eq!(eval_str("[]", &mut context)?, TalkValue::List(vec![]));
+        assert_eq!(eval_str("[1, \"two\", x > 1,]", &mut context)?,
+                   TalkValue::List(vec![1.into(), TalkValue::new_str("two"), TalkValue::Bool(true)]));
+        assert_eq!(eval_str("[[x], [], [x * x, [null]]]", &mut context)?, TalkValue::List(vec![
+            TalkValue::List(vec![2.into()]),
+            TalkValue::List(vec![]),
+            TalkValue::List(vec![4.into(), TalkValue::List(vec![TalkValue::Null])]),
+        ]));
+        assert_eq!(eval_str("len([1, 2, 3])", &mut context)?, TalkValue::Int(3));
+        assert_eq!(eval_str("[1, -\"a\"]", &mut context).unwrap_err().text(), "cannot negate string");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitwise() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        eval_str("{ TALKED = 0b001; FOUGHT = 0b010; HELPED = 0b100; flags = 0 }", &mut context)?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+
+        assert_eq!(eval("flags = flags | TALKED | HELPED")?, TalkValue::Int(0b101));
+        assert_eq!(eval("flags & HELPED != 0")?, TalkValue::Bool(true));
+        assert_eq!(eval("flags & FOUGHT == 0")?, TalkValue::Bool(true));
+        assert_eq!(eval("flags = flags & ~TALKED")?, TalkValue::Int(0b100));
+        assert_eq!(eval("flags ^ (HELPED | FOUGHT)")?, TalkValue::Int(0b010));
+        assert_eq!(eval("0xFF & 0x0F | 0x30 ^ 0x10")?, TalkValue::Int(0x2F));
+        assert_eq!(eval("1 | 2 + 4")?, TalkValue::Int(7));
+
+        assert_eq!(eval("~0")?, TalkValue::Int(-1));
+        assert_eq!(eval("~-1")?, TalkValue::Int(0));
+        assert_eq!(eval("~5")?, TalkValue::Int(-6));
+        assert_eq!(eval("~(-9223372036854775807 - 1)")?, TalkValue::Int(i64::MAX));
+        assert_eq!(eval("-8 & 0xF")?, TalkValue::Int(8));
+        assert_eq!(eval("-1 ^ 5")?, TalkValue::Int(-6));
+
+        assert_eq!(eval("1 | true").unwrap_err().text(), "BitOr expects int operands, got bool");
+        assert_eq!(eval("1.0 & 1").unwrap_err().text(), "BitAnd expects int operands, got float");
+        assert_eq!(eval("\"a\" ^ 1").unwrap_err().text(), "BitXor expects int operands, got string");
+        assert_eq!(eval("~true").unwrap_err().text(), "expected int, got bool");
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let mut eval = |src: &str| eval_str(src, &mut context);
+        assert_eq!(eval("1 << 4")?, TalkValue::Int(16));
+        assert_eq!(eval("0xF0 >> 4")?, TalkValue::Int(0xF));
+        assert_eq!(eval("1 << 3 | 1 << 1")?, TalkValue::Int(0b1010));
+        assert_eq!(eval("1 << 2 + 1")?, TalkValue::Int(8));
+        assert_eq!(eval("x = 5 << 0")?, TalkValue::Int(5));
+
+        // Bits shifted out are dropped; `>>` keeps the sign.
+        assert_eq!(eval("1 << 63")?, TalkValue::Int(i64::MIN));
+        assert_eq!(eval("3 << 63")?, TalkValue::Int(i64::MIN));
+        assert_eq!(eval("-8 >> 1")?, TalkValue::Int(-4));
+        assert_eq!(eval("-1 >> 63")?, TalkValue::Int(-1));
+        assert_eq!(eval("(1 << 63) >> 63")?, TalkValue::Int(-1));
+
+        assert_eq!(eval("1 << 64").unwrap_err().text(), "Shift amount must be between 0 and 63, got 64");
+        assert_eq!(eval("1 >> 64").unwrap_err().text(), "Shift amount must be between 0 and 63, got 64");
+        assert_eq!(eval("1 << -1").unwrap_err().text(), "Shift amount must be between 0 and 63, got -1");
+        assert_eq!(eval("1 << true").unwrap_err().text(), "Shl expects int operands, got bool");
+        Ok(())
+    }
+
+    #[test]
+    fn test_xor() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("calls", TalkValue::new_int(0))?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+        assert_eq!(eval("false xor false")?, TalkValue::Bool(false));
+        assert_eq!(eval("false xor true")?, TalkValue::Bool(true));
+        assert_eq!(eval("true xor false")?, TalkValue::Bool(true));
+        assert_eq!(eval("true xor true")?, TalkValue::Bool(false));
+        assert_eq!(eval("1 xor \"\"")?, TalkValue::Bool(true));
+        assert_eq!(eval("[0] xor null")?, TalkValue::Bool(true));
+        assert_eq!(eval("true xor true xor true")?, TalkValue::Bool(true));
+
+        // Both sides run, unlike with `||` and `&&`.
+        assert_eq!(eval("true xor (calls = calls + 1)")?, TalkValue::Bool(false));
+        assert_eq!(eval("false xor (calls = calls + 1)")?, TalkValue::Bool(true));
+        assert_eq!(eval("true || (calls = calls + 1)")?, TalkValue::Bool(true));
+        assert_eq!(eval("calls")?, TalkValue::Int(2));
+        assert_eq!(eval("true xor missing()").unwrap_err().text(), "Undefined function missing");
+        Ok(())
+    }
+
+    #[test]
+    fn test_typeof() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let mut eval = |src: &str| eval_str(src, &mut context);
+        assert_eq!(eval("typeof 1")?, TalkValue::new_str("int"));
+        assert_eq!(eval("typeof 1.5")?, TalkValue::new_str("float"));
+        assert_eq!(eval("typeof \"a\"")?, TalkValue::new_str("string"));
+        assert_eq!(eval("typeof false")?, TalkValue::new_str("bool"));
+        assert_eq!(eval("typeof null")?, TalkValue::new_str("null"));
+        assert_eq!(eval("typeof missing")?, TalkValue::new_str("null"));
+        assert_eq!(eval("typeof [1]")?, TalkValue::new_str("list"));
+        assert_eq!(eval("typeof { a: 1 }")?, TalkValue::new_str("object"));
+
+        assert_eq!(eval("typeof { a: 1 }.a == \"int\"")?, TalkValue::Bool(true));
+        assert_eq!(eval("typeof typeof 1")?, TalkValue::new_str("string"));
+        assert_eq!(eval("typeof -(0.5)")?, TalkValue::new_str("float"));
+        assert_eq!(eval("typeof 1 + 1")?, TalkValue::new_str("int1"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "sync"), allow(clippy::arc_with_non_send_sync))]
+    fn test_defined() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::builder().auto_vivify(true).build();
+        eval_str("{ player = { hp: 0, pet: null, quest: { stage: 1 } }; in_town = false }", &mut context)?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+
+        assert_eq!(eval("defined(foo)")?, TalkValue::Bool(false));
+        assert_eq!(eval("defined(in_town)")?, TalkValue::Bool(true));
+        assert_eq!(eval("defined(player.hp)")?, TalkValue::Bool(true));
+        assert_eq!(eval("defined(player.quest.stage)")?, TalkValue::Bool(true));
+        assert_eq!(eval("defined(player.pet)")?, TalkValue::Bool(false));
+        assert_eq!(eval("defined(player.pet.name)")?, TalkValue::Bool(false));
+        assert_eq!(eval("defined(player.hp.max)")?, TalkValue::Bool(false));
+        assert_eq!(eval("defined(foo.bar.baz)")?, TalkValue::Bool(false));
+        assert_eq!(eval("defined(player?.mount)")?, TalkValue::Bool(false));
+
+        // Unlike the lenient `get`, `defined` leaves no phantom fields behind.
+        assert!(!context.contains("foo"));
+        assert!(!context.get_path("player").unwrap().as_object().unwrap().contains("mount"));
+        assert_eq!(context.get("foo").cloned(), Some(TalkValue::new_obj()));
+        assert!(context.contains("foo"));
+
+        // Nor does it copy what it finds in a base.
+        let mut template = TalkObject::new();
+        eval_str("stats = { hp: 10, bonus: null }", &mut template)?;
+        let mut goblin = TalkObject::new();
+        goblin.set_base(std::sync::Arc::new(template));
+        assert_eq!(eval_str("defined(stats) && defined(stats.hp) && !defined(stats.bonus)", &mut goblin)?, TalkValue::Bool(true));
+        assert_eq!(goblin.local_len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesce() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        eval_str("{ player = { nickname: null, level: 0, name: \"\" }; calls = 0 }", &mut context)?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+
+        assert_eq!(eval("player.nickname ?? \"stranger\"")?, TalkValue::new_str("stranger"));
+        assert_eq!(eval("player.missing ?? \"stranger\"")?, TalkValue::new_str("stranger"));
+        assert_eq!(eval("player.level ?? 5")?, TalkValue::Int(0));
+        assert_eq!(eval("player.level || 5")?, TalkValue::Int(5));
+        assert_eq!(eval("player.name ?? \"x\"")?, TalkValue::new_str(""));
+        assert_eq!(eval("player.nickname || 5")?, TalkValue::Int(5));
+        assert_eq!(eval("null ?? null ?? 3")?, TalkValue::Int(3));
+        assert_eq!(eval("null ?? null")?, TalkValue::Null);
+        assert_eq!(eval("0 ?? 5 ? \"yes\" : \"no\"")?, TalkValue::new_str("no"));
+
+        // The right side only runs for a `Null` left side.
+        assert_eq!(eval("1 ?? (calls = calls + 1)")?, TalkValue::Int(1));
+        assert_eq!(eval("calls")?, TalkValue::Int(0));
+        assert_eq!(eval("1 ?? missing()")?, TalkValue::Int(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_optional_field() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::builder().auto_vivify(true).build();
+        eval_str("{ player = { quest: { stage: 3 }, pet: null, hp: 7 }; nobody = null }", &mut context)?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+
+        assert_eq!(eval("player?.quest?.stage")?, TalkValue::Int(3));
+        assert_eq!(eval("player.quest?.stage + 1")?, TalkValue::Int(4));
+        assert_eq!(eval("player?.pet?.name")?, TalkValue::Null);
+        assert_eq!(eval("player?.mount?.name")?, TalkValue::Null);
+        assert_eq!(eval("nobody?.quest?.stage")?, TalkValue::Null);
+        assert_eq!(eval("ghost?.quest?.stage ?? \"none\"")?, TalkValue::new_str("none"));
+        assert_eq!(eval("{ a: null }?.a?.b")?, TalkValue::Null);
+        assert_eq!(eval("{ a: { b: 1 } }.a.b")?, TalkValue::Int(1));
+
+        // Only the guarded step short-circuits.
+        assert_eq!(eval("player?.pet.name").unwrap_err().text(), "player?.pet is not an object, got null");
+        assert_eq!(eval("player.hp?.max").unwrap_err().text(), "player.hp is not an object, got int");
+        assert_eq!(eval("(1 + 1)?.x").unwrap_err().text(), "expression is not an object, got int");
+
+        // Missing steps are not created, even though the context auto-vivifies.
+        assert!(!context.contains("ghost"));
+        assert_eq!(context.get_path("player.mount").cloned(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        eval_str("{ inventory = [\"sword\", \"shield\"]; data = { gold: 5, \"key var\": [1, [2]] }; slot = 1; name = \"gold\" }", &mut context)?;
+
+        assert_eq!(eval_str("inventory[slot]", &mut context)?, TalkValue::new_str("shield"));
+        assert_eq!(eval_str("inventory[slot - 1]", &mut context)?, TalkValue::new_str("sword"));
+        assert_eq!(eval_str("data[name] + 1", &mut context)?, TalkValue::Int(6));
+        assert_eq!(eval_str("data[\"key var\"][1][0]", &mut context)?, TalkValue::Int(2));
+        assert_eq!(eval_str("data[\"missing\"]", &mut context)?, TalkValue::Null);
+        assert_eq!(eval_str("[10, 20, 30][2]", &mut context)?, TalkValue::Int(30));
+        assert_eq!(eval_str("{ a: 1 }[\"a\"]", &mut context)?, TalkValue::Int(1));
+        let expr = crate::parser::parse("inventory[0]").unwrap();
+        assert!(matches!(expr.eval(&mut context)?, Cow::Borrowed(_)));
+
+        let err = |src: &str, context: &mut TalkObject| eval_str(src, context).unwrap_err().text();
+        assert_eq!(err("inventory[2]", &mut context), "Index 2 out of bounds");
+        assert_eq!(err("inventory[-1]", &mut context), "Index -1 out of bounds");
+        assert_eq!(err("inventory[\"0\"]", &mut context), "expected int, got string");
+        assert_eq!(err("data[1]", &mut context), "expected string, got int");
+        assert_eq!(err("slot[0]", &mut context), "cannot index int");
+        assert_eq!(err("\"abc\"[0]", &mut context), "cannot index string");
+        assert_eq!(err("nothing[0]", &mut context), "cannot index null");
+        Ok(())
+    }
+
+    #[test]
+    fn test_in() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        eval_str("{ bag = [\"sword\", 2, [3]]; stats = { hp: 10 }; name = \"Sir Robin\" }", &mut context)?;
+        let mut eval = |src: &str| eval_str(src, &mut context);
+
+        assert_eq!(eval("\"sword\" in bag")?, TalkValue::Bool(true));
+        assert_eq!(eval("[3] in bag")?, TalkValue::Bool(true));
+        assert_eq!(eval("\"2\" in bag")?, TalkValue::Bool(false));
+        assert_eq!(eval("\"hp\" in stats")?, TalkValue::Bool(true));
+        assert_eq!(eval("\"mp\" in stats")?, TalkValue::Bool(false));
+        assert_eq!(eval("\"Robin\" in name")?, TalkValue::Bool(true));
+        assert_eq!(eval("\"robin\" in name")?, TalkValue::Bool(false));
+        assert_eq!(eval("\"\" in \"\"")?, TalkValue::Bool(true));
+        assert_eq!(eval("!(1 in [])")?, TalkValue::Bool(true));
+
+        assert_eq!(eval("1 in stats").unwrap_err().text(), "expected string, got int");
+        assert_eq!(eval("1 in name").unwrap_err().text(), "expected string, got int");
+        assert_eq!(eval("1 in 10").unwrap_err().text(), "expected list, object or string, got int");
+        assert_eq!(eval("1 in missing").unwrap_err().text(), "expected list, object or string, got null");
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_proxy_keys() {
+        let mut proxy = StatsProxy { hp: TalkValue::new_int(10), name: TalkValue::new_str("Robin") };
+        let mut stats = TalkObject::new();
+        stats.push_proxy(&mut proxy);
+        let mut context = TalkObject::new();
+        context.set("stats", TalkValue::Object(Box::new(stats))).unwrap();
+        assert_eq!(eval_str("\"hp\" in stats", &mut context).unwrap(), TalkValue::Bool(true));
+        assert_eq!(eval_str("\"mp\" in stats", &mut context).unwrap(), TalkValue::Bool(false));
+    }
+
+    #[test]
+    fn test_object_literal() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("level", TalkValue::new_int(3))?;
+        let bob = eval_str("{ name: \"Bob\", hp: level * 10, \"is boss\": false }", &mut context)?;
+        let mut expected = TalkObject::new();
+        expected.set("name", TalkValue::new_str("Bob"))?;
+        expected.set("hp", TalkValue::new_int(30))?;
+        expected.set("is boss", TalkValue::Bool(false))?;
+        assert_eq!(bob, TalkValue::Object(Box::new(expected)));
+
+        eval_str("player = { name: \"Ann\", pos: { x: 1, y: level }, bag: [{ id: 7 }] }", &mut context)?;
+        assert_eq!(eval_str("player.pos.y + player.pos.x", &mut context)?, TalkValue::Int(4));
+        assert_eq!(context.get_path("player.name").cloned(), Some(TalkValue::new_str("Ann")));
+        assert_eq!(eval_str("{}", &mut context)?, TalkValue::new_obj());
+        assert_eq!(eval_str("{ a: missing() }", &mut context).unwrap_err().text(), "Undefined function missing");
+
+        // Only the parser rejects duplicate keys; otherwise the last value wins.
+        let expr = Expression::Object(vec![("k".to_string(), *int(1)), ("k".to_string(), *int(2))]);
+        assert_eq!(expr.eval(&mut context)?.to_string(), "{k: 2}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_in() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("sum", TalkValue::new_int(0))?;
+        context.set("list", TalkValue::List(vec![1.into(), 2.into(), 3.into()]))?;
+        let accumulate = Box::new(Expression::Assign { target: ident("sum"), value: binary(BinOp::Add, ident("sum"), ident("x")) });
+
+        let expr = Expression::ForIn { var: "x".to_string(), iterable: ident("list"), body: accumulate.clone() };
+        assert_eq!(*expr.eval(&mut context)?, TalkValue::Int(6));
+        assert_eq!(*context.get("sum").unwrap(), TalkValue::Int(6));
+        assert!(!context.map.contains_key("x"));
+        assert_eq!(context.scopes.depth(), 0);
+
+        // The loop variable shadows a field of the same name, which is left as it was.
+        context.set("x", TalkValue::new_str("outer"))?;
+        expr.eval(&mut context)?;
+        assert_eq!(*context.get("sum").unwrap(), TalkValue::Int(12));
+        assert_eq!(*context.get("x").unwrap(), TalkValue::new_str("outer"));
+
+        let expr = Expression::ForIn { var: "x".to_string(), iterable: int(3), body: accumulate };
+        assert_eq!(expr.eval(&mut context).unwrap_err().text(), "expected list, got int");
+
+        // The frame is popped even when the body fails.
+        let failing = Box::new(Expression::Binary { op: BinOp::Div, lhs: ident("x"), rhs: int(0) });
+        let expr = Expression::ForIn { var: "y".to_string(), iterable: ident("list"), body: failing };
+        assert!(expr.eval(&mut context).is_err());
+        assert_eq!(context.scopes.depth(), 0);
+        assert!(context.lookup("y").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let ints = |values: &[i64]| TalkValue::List(values.iter().map(|&i| i.into()).collect());
+        assert_eq!(eval_str("1..4", &mut context)?, ints(&[1, 2, 3]));
+        assert_eq!(eval_str("1..=4", &mut context)?, ints(&[1, 2, 3, 4]));
+        assert_eq!(eval_str("-2..0", &mut context)?, ints(&[-2, -1]));
+        assert_eq!(eval_str("3..3", &mut context)?, ints(&[]));
+        assert_eq!(eval_str("3..=3", &mut context)?, ints(&[3]));
+        assert_eq!(eval_str("5..1", &mut context)?, ints(&[]));
+        assert_eq!(eval_str("5..=1", &mut context)?, ints(&[]));
+        assert_eq!(eval_str("9223372036854775806..=9223372036854775807", &mut context)?, ints(&[i64::MAX - 1, i64::MAX]));
+
+        context.set("sum", TalkValue::new_int(0))?;
+        let accumulate = Box::new(Expression::Assign { target: ident("sum"), value: binary(BinOp::Add, ident("sum"), ident("i")) });
+        let sum_over = |src: &str| Expression::ForIn { var: "i".to_string(), iterable: Box::new(crate::parser::parse(src).unwrap()), body: accumulate.clone() };
+        assert_eq!(*sum_over("1..10").eval(&mut context)?, TalkValue::Int(45));
+        assert_eq!(*sum_over("1..=10").eval(&mut context)?, TalkValue::Int(100));
+        assert_eq!(*sum_over("10..1").eval(&mut context)?, TalkValue::Null);
+        assert_eq!(*context.get("sum").unwrap(), TalkValue::Int(100));
+
+        assert_eq!(eval_str("1..2.5", &mut context).unwrap_err().text(), "expected int, got float");
+        context.set_max_iterations(100);
+        assert_eq!(eval_str("0..=100", &mut context).unwrap_err().text(), "Range 0..=100 exceeds 100 elements");
+        assert!(eval_str("0..100", &mut context).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_call() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        ctx.register_fn("add", |args| {
+            let mut sum = 0;
+            for arg in args {
+                sum += arg.into_int()?;
+            }
+            Ok(TalkValue::Int(sum))
+        });
+
+        assert_eq!(eval_str("add(1, 2) * 2", &mut ctx)?, TalkValue::Int(6));
+        assert_eq!(eval_str("add()", &mut ctx)?, TalkValue::Int(0));
+        assert_eq!(eval_str("add(\"x\")", &mut ctx).unwrap_err().text(), "expected int, got string");
+
+        let err = eval_str("roll(6)", &mut ctx).unwrap_err();
+        assert_eq!(err.kind, TalkEvalErrorKind::UndefinedFunction("roll".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_mod_semantics() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        assert_eq!(eval_str("-7 / 2", &mut ctx)?, TalkValue::Int(-3));
+        assert_eq!(eval_str("-7 % 2", &mut ctx)?, TalkValue::Int(-1));
+        assert_eq!(eval_str("7 % -2", &mut ctx)?, TalkValue::Int(1));
+        assert_eq!(eval_str("-7 % -2", &mut ctx)?, TalkValue::Int(-1));
+        assert_eq!(eval_str("7 / 0", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::DivisionByZero);
+        assert_eq!(eval_str("7 % 0", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::DivisionByZero);
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> Result<(), TalkEvalError> {
+        let mut ctx = TalkObject::new();
+        ctx.set("max", TalkValue::Int(i64::MAX))?;
+        ctx.set("min", TalkValue::Int(i64::MIN))?;
+
+        assert_eq!(eval_str("max + 1", &mut ctx).unwrap_err().text(), "Integer overflow in 9223372036854775807 + 1");
+        assert_eq!(eval_str("min - 1", &mut ctx).unwrap_err().text(), "Integer overflow in -9223372036854775808 - 1");
+        assert!(matches!(eval_str("max * 2", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::Overflow(_)));
+        assert!(matches!(eval_str("min * -1", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::Overflow(_)));
+        assert!(matches!(eval_str("min / -1", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::Overflow(_)));
+        assert!(matches!(eval_str("min % -1", &mut ctx).unwrap_err().kind, TalkEvalErrorKind::Overflow(_)));
+        assert_eq!(eval_str("-min", &mut ctx).unwrap_err().text(), "Integer overflow in -(-9223372036854775808)");
+
+        assert_eq!(eval_str("max - 1 + 1", &mut ctx)?, TalkValue::Int(i64::MAX));
+        assert_eq!(eval_str("-max - 1", &mut ctx)?, TalkValue::Int(i64::MIN));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone() -> Result<(), TalkEvalError> {
+        let mut inner = TalkObject::new();
+        inner.set("hp", TalkValue::new_int(10))?;
+        let mut original = TalkObject::new();
+        original.set("player", TalkValue::Object(Box::new(inner)))?;
+        original.set("items", TalkValue::List(vec!["key".into()]))?;
+
+        let mut copy = original.clone();
+        copy.get("player").unwrap().as_object()?.set("hp", TalkValue::new_int(0))?;
+        copy.get("items").unwrap().set_index(0, "map".into())?;
+        copy.set("extra", TalkValue::Null)?;
+
+        assert_eq!(*original.get("player").unwrap().as_object()?.get("hp").unwrap(), TalkValue::Int(10));
+        assert_eq!(original.get("items").unwrap().get_index(0), Some(&TalkValue::new_str("key")));
+        assert!(!original.map.contains_key("extra"));
+
+        let mut dummy = DummyProxy::new();
+        let mut proxied = TalkObject::builder().auto_vivify(true).max_depth(3).build();
+        proxied.push_proxy(&mut dummy);
+        let copy = proxied.clone();
+        assert!(copy.proxies.is_empty());
+        assert!(copy.auto_vivify);
+        assert_eq!(copy.max_depth(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut inner = TalkObject::new();
+        inner.set("flag", TalkValue::Bool(true)).unwrap();
+        inner.set("count", TalkValue::new_int(1)).unwrap();
+        let mut outer = TalkObject::new();
+        outer.set("inner", TalkValue::Object(Box::new(inner))).unwrap();
+
+        let samples = [
+            TalkValue::new_int(1),
+            TalkValue::new_int(0),
+            TalkValue::new_float(1.0),
+            TalkValue::new_str("true"),
+            TalkValue::new_str("1"),
+            TalkValue::Bool(true),
+            TalkValue::Bool(false),
+            TalkValue::Null,
+            TalkValue::List(vec![TalkValue::Bool(true), TalkValue::new_int(1), TalkValue::Null]),
+            TalkValue::Object(Box::new(outer)),
+        ];
+        for value in samples.iter() {
+            let json = serde_json::to_string(value).unwrap();
+            let back: TalkValue = serde_json::from_str(&json).unwrap();
+            assert_eq!(&back, value, "{}", json);
+        }
+
+        #[cfg(not(feature = "tagged-serde"))]
+        {
+            let back: TalkValue = serde_json::from_str("true").unwrap();
+            assert_eq!(back.as_bool_opt(), Some(true));
+            assert_eq!(back.as_int(), None);
+        }
+    }
+
+    #[cfg(feature = "tagged-serde")]
+    #[test]
+    fn test_tagged_serde() {
+        assert_eq!(serde_json::to_string(&TalkValue::new_int(42)).unwrap(), r#"{"type":"int","value":42}"#);
+        assert_eq!(serde_json::to_string(&TalkValue::Bool(true)).unwrap(), r#"{"type":"bool","value":true}"#);
+        assert_eq!(serde_json::to_string(&TalkValue::Null).unwrap(), r#"{"type":"null"}"#);
+
+        let mut inner = TalkObject::new();
+        inner.set("flag", TalkValue::Bool(true)).unwrap();
+        let mut outer = TalkObject::new();
+        outer.set("inner", TalkValue::Object(Box::new(inner))).unwrap();
+        outer.set("items", TalkValue::List(vec![TalkValue::new_float(1.0), TalkValue::new_str("1")])).unwrap();
+        let value = TalkValue::Object(Box::new(outer));
+
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(json["value"]["inner"], serde_json::json!({"type": "object", "value": {"flag": {"type": "bool", "value": true}}}));
+
+        let samples = [
+            TalkValue::new_int(1),
+            TalkValue::new_float(1.0),
+            TalkValue::new_float(f64::MIN_POSITIVE),
+            TalkValue::new_str("true"),
+            TalkValue::Bool(false),
+            TalkValue::Null,
+            TalkValue::List(vec![TalkValue::Bool(true), TalkValue::new_int(1), TalkValue::Null]),
+            value,
+        ];
+        for value in samples.iter() {
+            let json = serde_json::to_string(value).unwrap();
+            let back: TalkValue = serde_json::from_str(&json).unwrap();
+            assert_eq!(&back, value, "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_resolve() -> Result<(), TalkEvalError> {
+        let mut stats = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+        let mut player = TalkObject::new();
+        player.push_proxy(&mut stats);
+        let mut dummy = DummyProxy::new();
+        let mut opaque = TalkObject::new();
+        opaque.push_proxy(&mut dummy);
+
+        let mut world = TalkObject::new();
+        world.set("player", TalkValue::Object(Box::new(player)))?;
+        world.set("opaque", TalkValue::Object(Box::new(opaque)))?;
+
+        let resolved = world.resolve()?;
+        assert_eq!(resolved.to_string(), "{opaque: {}, player: {hp: 7, name: Bob}}");
+
+        #[cfg(not(feature = "tagged-serde"))]
+        {
+            // Plain serialization drops proxied state.
+            let plain = serde_json::to_value(&world).unwrap();
+            assert_eq!(plain, serde_json::json!({"player": {}, "opaque": {}}));
+
+            let resolved = serde_json::to_value(&resolved).unwrap();
+            assert_eq!(resolved, serde_json::json!({"player": {"hp": 7, "name": "Bob"}, "opaque": {}}));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eq_resolved() -> Result<(), TalkEvalError> {
+        let mut stats = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+        let mut proxied = TalkObject::builder().auto_vivify(true).build();
+        proxied.push_proxy(&mut stats);
+        let mut literal = TalkObject::builder().auto_vivify(true).build();
+        literal.set("hp", TalkValue::new_int(7))?;
+        literal.set("name", TalkValue::new_str("Bob"))?;
+        assert!(proxied != literal);
+        assert!(proxied.eq_resolved(&mut literal));
+
+        let mut outer_proxied = TalkObject::new();
+        outer_proxied.set("player", TalkValue::Object(Box::new(proxied)))?;
+        let mut outer_literal = TalkObject::new();
+        outer_literal.set("player", TalkValue::Object(Box::new(literal.clone())))?;
+        assert!(outer_proxied.eq_resolved(&mut outer_literal));
+
+        literal.set("level", TalkValue::new_int(1))?;
+        outer_literal.set("player", TalkValue::Object(Box::new(literal)))?;
+        assert!(!outer_proxied.eq_resolved(&mut outer_literal));
+        // Neither side picked up the other's fields.
+        assert!(!outer_proxied.get("player").unwrap().as_object()?.map.contains_key("level"));
+        assert_eq!(outer_literal.get("player").unwrap().as_object()?.local_len(), 3);
+        Ok(())
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_send_to_thread() -> Result<(), TalkEvalError> {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TalkObject>();
+
+        let mut stats = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+        let mut context = TalkObject::new();
+        context.push_proxy(&mut stats);
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        context.register_fn("heal", move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(TalkValue::Int(5))
+        });
+
+        // A scoped thread may borrow `stats` through the context.
+        let hp = std::thread::scope(|scope| {
+            scope.spawn(move || eval_str("hp = hp + heal()", &mut context)).join().unwrap()
+        })?;
+        // Read back through the proxy, which got the write.
+        assert_eq!(hp, TalkValue::Int(12));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_paths() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        eval_str("a = 1", &mut context)?;
+        context.set_path("world.player.name", TalkValue::new_str("Bob"))?;
+        context.set_path("world.player.hp", TalkValue::new_int(10))?;
+        assert_eq!(context.to_string(), "{a: 1, world: {player: {hp: 10, name: Bob}}}");
+        assert_eq!(eval_str("world.player.hp", &mut context)?, TalkValue::Int(10));
+
+        *context.get_path("world.player.hp").unwrap() = TalkValue::new_int(9);
+        assert_eq!(context.get_path("world.player.hp"), Some(&mut TalkValue::Int(9)));
+        assert_eq!(context.get_path("world.player.mp"), None);
+        assert_eq!(context.get_path("a.b"), None);
+        assert_eq!(context.get_path(""), None);
+
+        // Intermediates resolve through proxies as well.
+        let mut stats = StatsProxy { hp: TalkValue::new_obj(), name: TalkValue::new_str("Bob") };
+        let mut proxied = TalkObject::new();
+        proxied.push_proxy(&mut stats);
+        proxied.set_path("hp.max", TalkValue::new_int(20))?;
+        assert_eq!(proxied.get_path("hp.max"), Some(&mut TalkValue::Int(20)));
+
+        let err = context.set_path("world.player.name.first", TalkValue::new_str("Bob")).unwrap_err();
+        assert_eq!(err.text(), "world.player.name is not an object, got string");
+        assert_eq!(context.set_path("a.b", TalkValue::Null).unwrap_err().text(), "a is not an object, got int");
+        assert_eq!(context.set_path("world..hp", TalkValue::Null).unwrap_err().text(), "Invalid path \"world..hp\"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_clone() -> Result<(), TalkEvalError> {
+        let snapshot: TalkObject<'static> = {
+            let mut stats = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+            let mut player = TalkObject::builder().max_depth(3).build();
+            player.push_proxy(&mut stats);
+            player.set("level", TalkValue::new_int(2))?;
+            player.set("hp", TalkValue::new_int(9))?;
+            let mut world = TalkObject::new();
+            world.set("player", TalkValue::Object(Box::new(player)))?;
+            world.set("tags", TalkValue::List(vec![TalkValue::new_str("new"), TalkValue::new_obj()]))?;
+
+            let copy = world.deep_clone();
+            // `PartialEq` would need both sides to share a lifetime, pinning `world` to `'static`.
+            assert_eq!(copy.to_string(), world.to_string());
+            assert_eq!(world.get("player").unwrap().as_object()?.proxies.len(), 1);
+            copy
+            // `stats` and `world` are dropped here; the copy outlives them.
+        };
+
+        let mut snapshot = snapshot;
+        let player = snapshot.get("player").unwrap().as_object()?;
+        assert!(player.proxies.is_empty());
+        assert_eq!(player.to_string(), "{level: 2}");
+        assert_eq!(player.max_depth(), 3);
+        assert_eq!(snapshot.to_string(), "{player: {level: 2}, tags: [new, {}]}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_depth_limit() {
+        let mut value = TalkValue::new_obj();
+        for _ in 0..MAX_RESOLVE_DEPTH + 1 {
+            let mut outer = TalkObject::new();
+            outer.set("next", value).unwrap();
+            value = TalkValue::Object(Box::new(outer));
+        }
+        assert!(value.as_object().unwrap().resolve().is_err());
+    }
+
+    #[cfg(not(feature = "tagged-serde"))]
+    #[test]
+    fn test_to_json() -> Result<(), TalkEvalError> {
+        let mut inner = TalkObject::new();
+        inner.set("hp", TalkValue::new_int(7))?;
+        let mut outer = TalkObject::new();
+        outer.set("player", TalkValue::Object(Box::new(inner)))?;
+
+        assert_eq!(outer.to_json()?, r#"{"player":{"hp":7}}"#);
+        assert_eq!(outer.to_json_pretty()?, "{\n  \"player\": {\n    \"hp\": 7\n  }\n}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut o = TalkObject::new();
+        o.set("quest_done", TalkValue::Bool(true)).unwrap();
+        assert_eq!(o.remove("quest_done"), Some(TalkValue::Bool(true)));
+        assert!(o.get("quest_done").is_none());
+        assert_eq!(o.remove("quest_done"), None);
+
+        // Proxies without removal support keep their fields.
+        let mut proxy = DummyProxy::new();
+        let mut o = TalkObject::new();
+        o.push_proxy(&mut proxy);
+        assert_eq!(o.remove("42"), None);
+        assert!(o.get("42").is_some());
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut o = TalkObject::new();
+        o.set("b", TalkValue::new_int(2)).unwrap();
+        o.set("c", TalkValue::new_int(3)).unwrap();
+        o.set("a", TalkValue::new_int(1)).unwrap();
+        assert_eq!(o.keys(), vec!["a", "b", "c"]);
+
+        let mut stats = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+        let mut dummy = DummyProxy::new();
+        o.push_proxy(&mut stats);
+        o.push_proxy(&mut dummy);
+        o.set("hp", TalkValue::new_int(8)).unwrap();
+        assert_eq!(o.keys(), vec!["a", "b", "c", "hp", "name"]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut o = TalkObject::builder().auto_vivify(true).build();
+        o.set("a", TalkValue::new_int(1)).unwrap();
+        assert!(o.contains("a"));
+        assert!(!o.contains("b"));
+        assert!(!o.contains("b"));
+        assert_eq!(o.local_len(), 1);
+
+        // Auto-vivifying `get` is what creates fields.
+        o.get("b");
+        assert!(o.contains("b"));
+    }
+
+    #[test]
+    fn test_parent_scope() -> Result<(), TalkEvalError> {
+        let mut outer = TalkObject::new();
+        outer.set("x", TalkValue::new_int(1))?;
+        let mut inner = TalkObject::builder().auto_vivify(true).build();
+        inner.set_parent(&mut outer);
+
+        assert_eq!(eval_str("x + 1", &mut inner)?, TalkValue::new_int(2));
+        assert_eq!(inner.get("x"), Some(&mut TalkValue::new_int(1)));
+        assert!(!inner.contains("x"));
+
+        // Writes stay local, shadowing the outer binding.
+        eval_str("y = 2", &mut inner)?;
+        eval_str("x = 10", &mut inner)?;
+        assert_eq!(eval_str("x", &mut inner)?, TalkValue::new_int(10));
+        let outer = inner.parent.as_mut().unwrap();
+        assert!(!outer.contains("y"));
+        assert_eq!(outer.lookup("x"), Some(&mut TalkValue::new_int(1)));
+
+        // Misses vivify locally, not in the parent.
+        assert!(inner.get("z").is_some());
+        assert!(inner.contains("z"));
+        assert!(!inner.parent.as_mut().unwrap().contains("z"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_proxy_priority() {
+        let mut flags = StatsProxy { hp: TalkValue::new_int(1), name: TalkValue::new_str("flags") };
+        let mut player = StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") };
+        let mut o = TalkObject::new();
+        o.push_proxy(&mut flags);
+        o.insert_proxy(0, &mut player);
+
+        assert_eq!(o.get("hp"), Some(&mut TalkValue::new_int(7)));
+
+        // Writes go to the first proxy defining the key, or stay local.
+        o.set("name", TalkValue::new_str("Alice")).unwrap();
+        o.set("gold", TalkValue::new_int(5)).unwrap();
+        assert_eq!(o.get("gold"), Some(&mut TalkValue::new_int(5)));
+        assert!(o.contains("gold"));
+        assert!(!o.contains("name"));
+        assert!(o.get("ghost").is_none());
+        assert_eq!(o.proxies[0].get("name"), Some(&mut TalkValue::new_str("Alice")));
+        assert_eq!(o.proxies[1].get("name"), Some(&mut TalkValue::new_str("flags")));
+
+        // Finding the proxy to write to doesn't vivify the key in one that auto-vivifies.
+        let mut lenient = TalkObject::builder().auto_vivify(true).build();
+        let mut o = TalkObject::new();
+        o.push_proxy(&mut lenient);
+        o.set("gold", TalkValue::new_int(5)).unwrap();
+        assert!(o.contains("gold"));
+        assert!(o.proxies[0].keys().is_empty());
+    }
+
+    #[test]
+    fn test_read_only_proxy() -> Result<(), TalkEvalError> {
+        let mut stats = ReadOnlyProxy(StatsProxy { hp: TalkValue::new_int(7), name: TalkValue::new_str("Bob") });
+        let mut context = TalkObject::new();
+        context.push_proxy(&mut stats);
+
+        assert_eq!(eval_str("hp + 1", &mut context)?, TalkValue::new_int(8));
+        let err = eval_str("hp = 1", &mut context).unwrap_err();
+        assert_eq!(err.kind, TalkEvalErrorKind::ReadOnly("hp".to_string()));
+        assert_eq!(err.text(), "Field hp is read-only");
+        assert!(context.set("name", TalkValue::Null).is_err());
+
+        assert_eq!(eval_str("hp", &mut context)?, TalkValue::new_int(7));
+        assert_eq!(eval_str("name", &mut context)?, TalkValue::new_str("Bob"));
+        assert_eq!(context.keys(), vec!["hp", "name"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scope_stack() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        context.set("x", TalkValue::new_int(1))?;
+        context.scopes().push();
+        context.scopes().bind("x", TalkValue::new_int(2));
+        context.scopes().bind("local", TalkValue::new_int(3));
+        context.scopes().push();
+
+        // Assignment updates the innermost binding, or the object if there is none.
+        assert_eq!(eval_str("x = x + 10", &mut context)?, TalkValue::new_int(12));
+        eval_str("total = local", &mut context)?;
+        assert_eq!(context.scopes().get_mut("x"), Some(&mut TalkValue::new_int(12)));
+        assert!(context.contains("total"));
+        assert!(!context.contains("local"));
+
+        context.scopes().pop();
+        context.scopes().pop();
+        assert_eq!(eval_str("x", &mut context)?, TalkValue::new_int(1));
+        assert_eq!(eval_str("local", &mut context)?, TalkValue::Null);
+
+        // `let` creates a local that goes with its frame, while assigning an outer binding
+        // outlives it.
+        context.scopes().push();
+        context.scopes().bind("outer", TalkValue::new_int(1));
+        context.scopes().push();
+        assert_eq!(eval_str("let x = 5", &mut context)?, TalkValue::new_int(5));
+        eval_str("outer = x + 1", &mut context)?;
+        context.scopes().pop();
+        assert_eq!(context.scopes().get_mut("outer"), Some(&mut TalkValue::new_int(6)));
+        assert_eq!(eval_str("x", &mut context)?, TalkValue::new_int(1));
+        context.scopes().pop();
+        assert_eq!(eval_str("let y = 1", &mut context).unwrap_err().text(), "let y outside of any scope");
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_eval_depth() -> Result<(), TalkEvalError> {
+        let nest = |depth: usize| {
+            let mut expr = *int(1);
+            for _ in 0..depth {
+                expr = Expression::Neg { operand: Box::new(expr) };
+            }
+            expr
+        };
+        let mut context = TalkObject::new();
+        assert_eq!(*nest(DEFAULT_MAX_EVAL_DEPTH - 1).eval(&mut context)?, TalkValue::Int(-1));
+
+        let deep = nest(100_000);
+        assert_eq!(deep.eval(&mut context).unwrap_err().text(), "Maximum evaluation depth of 128 exceeded");
+        // The depth is released on the way out, errors or not.
+        assert_eq!(*nest(100).eval(&mut context)?, TalkValue::Int(1));
+
+        context.set_max_eval_depth(10);
+        assert_eq!(nest(10).eval(&mut context).unwrap_err().text(), "Maximum evaluation depth of 10 exceeded");
+        assert_eq!(eval_str("1 + 2 * 3", &mut context)?, TalkValue::Int(7));
+        let err = eval_str(&("-".repeat(11) + "1"), &mut context).unwrap_err();
+        assert_eq!(err.text(), "Expression is nested deeper than 10 levels");
+
+        // Parsed source may nest as deeply as the context allows, past the parser's default.
+        let parens = "(".repeat(40) + "-1" + &")".repeat(40);
+        assert!(eval_str(&parens, &mut TalkObject::new()).is_err());
+        let mut context = TalkObject::builder().max_eval_depth(48).build();
+        assert_eq!(eval_str(&parens, &mut context)?, TalkValue::Int(-1));
+        // Dropping nested boxes recurses as well, so tear the tree down iteratively.
+        let mut deep = deep;
+        while let Expression::Neg { operand } = deep {
+            deep = *operand;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_budget() -> Result<(), TalkEvalError> {
+        let run = |budget: u64| -> Result<TalkValue<'static>, TalkEvalError> {
+            let mut context = TalkObject::builder().max_iterations(usize::MAX).step_budget(budget).build();
+            context.set("i", TalkValue::new_int(0))?;
+            let increment = Box::new(Expression::Assign { target: ident("i"), value: binary(BinOp::Add, ident("i"), int(1)) });
+            let expr = Expression::While { cond: compare(CmpOp::Lt, ident("i"), int(1_000_000)), body: increment };
+            assert_eq!(expr.eval(&mut context).unwrap_err().text(), "Step budget exhausted");
+            assert_eq!(context.step_budget(), Some(0));
+            Ok(context.get("i").unwrap().clone())
+        };
+        // 1 step for the loop, then 8 per iteration: 3 for the condition, 1 for the
+        // iteration and 4 for the body, which runs out on its last literal.
+        assert_eq!(run(1_000)?, TalkValue::Int(124));
+        assert_eq!(run(1_000)?, TalkValue::Int(124));
+
+        let mut context = TalkObject::builder().step_budget(4).build();
+        assert_eq!(eval_str("1 + 2", &mut context)?, TalkValue::Int(3));
+        assert_eq!(context.step_budget(), Some(1));
+        assert!(eval_str("1 + 2", &mut context).is_err());
+        context.set_step_budget(None);
+        assert_eq!(eval_str("1 + 2", &mut context)?, TalkValue::Int(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_depth() -> Result<(), TalkEvalError> {
+        let mut context = TalkObject::new();
+        let mut c = TalkObject::new();
+        c.set("d", TalkValue::new_int(1))?;
+        let mut b = TalkObject::new();
+        b.set("c", TalkValue::Object(Box::new(c)))?;
+        let mut a = TalkObject::new();
+        a.set("b", TalkValue::Object(Box::new(b)))?;
+        context.set("a", TalkValue::Object(Box::new(a)))?;
+
+        context.set_max_depth(3);
+        assert_eq!(eval_str("a.b.c.d", &mut context)?, TalkValue::new_int(1));
+        assert_eq!(eval_str("a.b.c.d = 2", &mut context)?, TalkValue::new_int(2));
+        let err = eval_str("a.b.c.d.e", &mut context).unwrap_err();
+        assert_eq!(err.text(), "Field path is nested deeper than 3 levels");
+        assert!(eval_str("a.b.c.d.e = 1", &mut context).is_err());
+
+        let deep = format!("a{}", ".b".repeat(DEFAULT_MAX_DEPTH + 1));
+        let mut context = TalkObject::new();
+        assert!(eval_str(&deep, &mut context).unwrap_err().text().contains("deeper than"));
+        assert!(eval_str(&format!("{} = 1", deep), &mut context).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_name() {
+        let names: Vec<&str> = [
+            TalkValue::new_int(1),
+            TalkValue::new_float(1.0),
+            TalkValue::new_str("1"),
+            TalkValue::Bool(true),
+            TalkValue::Null,
+            TalkValue::new_list(),
+            TalkValue::new_obj(),
+        ].iter().map(TalkValue::type_name).collect();
+        assert_eq!(names, vec!["int", "float", "string", "bool", "null", "list", "object"]);
+        assert_eq!(TalkValue::new_str("1").into_int().unwrap_err().kind, TalkEvalErrorKind::TypeMismatch { expected: "int", got: "string" });
+    }
+
+    #[test]
+    fn test_loose_equality() -> Result<(), TalkEvalError> {
+        let mut strict = TalkObject::new();
+        assert_eq!(eval_str("1 == \"1\"", &mut strict)?, TalkValue::Bool(false));
+        assert_eq!(eval_str("1 != \"1\"", &mut strict)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("1 == 1.0", &mut strict)?, TalkValue::Bool(true));
 
+        let mut loose = TalkObject::builder().loose_equality(true).build();
+        assert_eq!(eval_str("1 == \"1\"", &mut loose)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("1 != \"1\"", &mut loose)?, TalkValue::Bool(false));
+        assert_eq!(eval_str("1.5 == \" 1.5 \"", &mut loose)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("true == \"true\"", &mut loose)?, TalkValue::Bool(true));
+        assert_eq!(eval_str("1 == \"one\"", &mut loose)?, TalkValue::Bool(false));
+        assert_eq!(eval_str("null == \"null\"", &mut loose)?, TalkValue::Bool(false));
+        // Ordering is unaffected.
+        assert!(eval_str("1 < \"2\"", &mut loose).is_err());
+        assert!(loose.clone().loose_equality);
         Ok(())
     }
 }
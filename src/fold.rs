@@ -0,0 +1,149 @@
+//! Constant folding: evaluates the parts of an AST that don't depend on the context ahead
+//! of time, so a line run every frame doesn't redo the same arithmetic.
+
+use crate::eval::{Eval, Expression, Literal, Spanned, TalkObject, TalkValue};
+
+/// Replaces every operator node whose operands are all literals with the literal it
+/// evaluates to, bottom-up, and resolves `?:`, `&&` and `||` whose deciding operand is a
+/// literal. Identifiers, calls and assignments are kept, though their operands are folded.
+/// A span around a node that folds to a literal is dropped, since a literal can't fail.
+///
+/// A sub-tree that fails to evaluate, such as `1 / 0`, is left as it is, so the error is
+/// still raised at runtime, if that code runs at all. Equality that `loose_equality` would
+/// decide differently, such as `1 == "1"`, is also left for the context to decide.
+pub fn fold_constants(expr: Expression) -> Expression {
+    let boxed = |e: Box<Expression>| Box::new(fold_constants(*e));
+    let expr = match expr {
+        Expression::Field { base, name } => Expression::Field { base: boxed(base), name },
+        Expression::OptionalField { base, name } => Expression::OptionalField { base: boxed(base), name },
+        Expression::Index { base, index } => Expression::Index { base: boxed(base), index: boxed(index) },
+        Expression::Binary { op, lhs, rhs } => Expression::Binary { op, lhs: boxed(lhs), rhs: boxed(rhs) },
+        Expression::Compare { op, lhs, rhs } => Expression::Compare { op, lhs: boxed(lhs), rhs: boxed(rhs) },
+        Expression::In { needle, haystack } => Expression::In { needle: boxed(needle), haystack: boxed(haystack) },
+        Expression::And { lhs, rhs } => match fold_constants(*lhs) {
+            Expression::Literal(l) if l.to_value().as_bool() => fold_constants(*rhs),
+            Expression::Literal(l) => Expression::Literal(l),
+            lhs => Expression::And { lhs: Box::new(lhs), rhs: boxed(rhs) },
+        },
+        Expression::Or { lhs, rhs } => match fold_constants(*lhs) {
+            Expression::Literal(l) if l.to_value().as_bool() => Expression::Literal(l),
+            Expression::Literal(_) => fold_constants(*rhs),
+            lhs => Expression::Or { lhs: Box::new(lhs), rhs: boxed(rhs) },
+        },
+        Expression::Xor { lhs, rhs } => Expression::Xor { lhs: boxed(lhs), rhs: boxed(rhs) },
+        Expression::Coalesce { lhs, rhs } => match fold_constants(*lhs) {
+            Expression::Literal(Literal::Null) => fold_constants(*rhs),
+            Expression::Literal(l) => Expression::Literal(l),
+            lhs => Expression::Coalesce { lhs: Box::new(lhs), rhs: boxed(rhs) },
+        },
+        Expression::Not { operand } => Expression::Not { operand: boxed(operand) },
+        Expression::Neg { operand } => Expression::Neg { operand: boxed(operand) },
+        Expression::BitNot { operand } => Expression::BitNot { operand: boxed(operand) },
+        Expression::TypeOf { operand } => Expression::TypeOf { operand: boxed(operand) },
+        Expression::Conditional { cond, then_branch, else_branch } => match fold_constants(*cond) {
+            Expression::Literal(l) if l.to_value().as_bool() => fold_constants(*then_branch),
+            Expression::Literal(_) => fold_constants(*else_branch),
+            cond => Expression::Conditional { cond: Box::new(cond), then_branch: boxed(then_branch), else_branch: boxed(else_branch) },
+        },
+        Expression::If { cond, then_block, else_block } => Expression::If {
+            cond: boxed(cond),
+            then_block: boxed(then_block),
+            else_block: else_block.map(boxed),
+        },
+        Expression::Match { scrutinee, arms, default } => Expression::Match {
+            scrutinee: boxed(scrutinee),
+            arms: arms.into_iter().map(|(pattern, body)| (fold_constants(pattern), fold_constants(body))).collect(),
+            default: default.map(boxed),
+        },
+        Expression::While { cond, body } => Expression::While { cond: boxed(cond), body: boxed(body) },
+        Expression::ForIn { var, iterable, body } => Expression::ForIn { var, iterable: boxed(iterable), body: boxed(body) },
+        Expression::Range { start, end, inclusive } => Expression::Range { start: boxed(start), end: boxed(end), inclusive },
+        Expression::List(items) => Expression::List(items.into_iter().map(fold_constants).collect()),
+        Expression::Object(entries) => Expression::Object(entries.into_iter().map(|(key, value)| (key, fold_constants(value))).collect()),
+        Expression::Block(statements) => Expression::Block(statements.into_iter().map(fold_constants).collect()),
+        Expression::Call { name, args } => Expression::Call { name, args: args.into_iter().map(fold_constants).collect() },
+        Expression::Assign { target, value } => Expression::Assign { target, value: boxed(value) },
+        Expression::Let { name, value } => Expression::Let { name, value: boxed(value) },
+        Expression::Spanned(spanned) => match fold_constants(spanned.node) {
+            Expression::Literal(l) => Expression::Literal(l),
+            node => Expression::Spanned(Box::new(Spanned { node, span: spanned.span })),
+        },
+        leaf @ Expression::Dummy | leaf @ Expression::Literal(_) | leaf @ Expression::Identifier(_) | leaf @ Expression::Defined { .. } => leaf,
+    };
+    try_fold(&expr).map_or(expr, Expression::Literal)
+}
+
+/// Evaluates an operator node over literals, under both strict and loose equality.
+fn try_fold(expr: &Expression) -> Option<Literal> {
+    let operands: Vec<&Expression> = match expr {
+        Expression::Binary { lhs, rhs, .. } | Expression::Compare { lhs, rhs, .. } | Expression::Xor { lhs, rhs } => vec![lhs, rhs],
+        Expression::Not { operand } | Expression::Neg { operand } | Expression::BitNot { operand } => vec![operand],
+        _ => return None,
+    };
+    if !operands.iter().all(|e| matches!(e, Expression::Literal(_))) {
+        return None;
+    }
+    let strict = expr.eval(&mut TalkObject::new()).ok()?.into_owned();
+    let loose = expr.eval(&mut TalkObject::builder().loose_equality(true).build()).ok()?.into_owned();
+    if strict != loose {
+        return None;
+    }
+    to_literal(strict)
+}
+
+fn to_literal(value: TalkValue) -> Option<Literal> {
+    match value {
+        TalkValue::Int(i) => Some(Literal::Int(i)),
+        TalkValue::Float(x) => Some(Literal::Float(x)),
+        TalkValue::String(s) => Some(Literal::String(s)),
+        TalkValue::Bool(b) => Some(Literal::Bool(b)),
+        TalkValue::Null => Some(Literal::Null),
+        TalkValue::List(_) | TalkValue::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{Expression, Literal};
+    use crate::fold::fold_constants;
+    use crate::parser::parse;
+
+    fn fold(src: &str) -> Expression { fold_constants(parse(src).unwrap()) }
+
+    fn lit(literal: Literal) -> Expression { Expression::Literal(literal) }
+
+    #[test]
+    fn test_fold_literals() {
+        assert_eq!(fold("2 + 3 * 4"), lit(Literal::Int(14)));
+        assert_eq!(fold("-(10 % 4) * (7 - 2)"), lit(Literal::Int(-10)));
+        assert_eq!(fold("0b110 & ~0b010 | 0x10"), lit(Literal::Int(0x14)));
+        assert_eq!(fold("\"HP: \" + 100"), lit(Literal::String("HP: 100".to_string())));
+        assert_eq!(fold("1 + 1 == 2 && !false"), lit(Literal::Bool(true)));
+        assert_eq!(fold("1 < 2 ? \"yes\" : \"no\""), lit(Literal::String("yes".to_string())));
+        assert_eq!(fold("null || 0 || 3"), lit(Literal::Int(3)));
+        assert_eq!(fold("null ?? 0 ?? 3"), lit(Literal::Int(0)));
+        assert_eq!(fold("1 xor \"\" xor true"), lit(Literal::Bool(false)));
+    }
+
+    #[test]
+    fn test_fold_preserves_context() {
+        assert_eq!(fold("x + 2 * 3"), parse("x + 6").unwrap());
+        assert_eq!(fold("player.hp = max(1 + 1, player.hp) - 0"), parse("player.hp = max(2, player.hp) - 0").unwrap());
+        assert_eq!(fold("{ x = 2 * 2; x }"), parse("{ x = 4; x }").unwrap());
+        assert_eq!(fold("[1 + 1, [x, 2 * 3]]"), parse("[2, [x, 6]]").unwrap());
+        assert_eq!(fold("true && ready"), parse("ready").unwrap());
+        assert_eq!(fold("false && ready"), lit(Literal::Bool(false)));
+        assert_eq!(fold("ready ? 1 + 1 : 0"), parse("ready ? 2 : 0").unwrap());
+        assert_eq!(fold("null ?? name"), parse("name").unwrap());
+        assert_eq!(fold("name ?? 1 + 1"), parse("name ?? 2").unwrap());
+    }
+
+    #[test]
+    fn test_fold_keeps_errors_and_context_dependent_equality() {
+        assert_eq!(fold("x + 1 / 0"), parse("x + 1 / 0").unwrap());
+        assert_eq!(fold("-(2 - 1) * (4 / (2 - 2))").to_source(), "-1 * (4 / 0)");
+        assert_eq!(fold("1 + \"a\" - 1"), parse("\"1a\" - 1").unwrap());
+        assert_eq!(fold("1 == \"1\""), parse("1 == \"1\"").unwrap());
+        assert_eq!(fold("1 == 1.0"), lit(Literal::Bool(true)));
+    }
+}
@@ -0,0 +1,46 @@
+//! Storage for `TalkObject` field names.
+//!
+//! With the `intern-keys` feature, names are `Arc<str>`s handed out from a process-wide
+//! pool, so a name like `"hp"` is allocated once however many objects use it. Without it,
+//! each object owns its names as `String`s. Either way, callers pass names as `&str`.
+//!
+//! The pool only grows. That suits field names, which come from a fixed vocabulary, but
+//! not data used as keys, such as player names. Names deserialized by serde bypass the pool.
+
+#[cfg(feature = "intern-keys")]
+pub type Key = std::sync::Arc<str>;
+
+#[cfg(not(feature = "intern-keys"))]
+pub type Key = String;
+
+#[cfg(feature = "intern-keys")]
+pub fn key(name: &str) -> Key {
+    use std::collections::HashSet;
+    use std::sync::{Mutex, OnceLock};
+
+    static POOL: OnceLock<Mutex<HashSet<Key>>> = OnceLock::new();
+    let mut pool = POOL.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    match pool.get(name) {
+        Some(key) => key.clone(),
+        None => {
+            let key = Key::from(name);
+            pool.insert(key.clone());
+            key
+        }
+    }
+}
+
+#[cfg(not(feature = "intern-keys"))]
+pub fn key(name: &str) -> Key { name.to_string() }
+
+#[cfg(test)]
+mod tests {
+    use crate::intern::key;
+
+    #[test]
+    fn test_key() {
+        assert_eq!(&*key("hp"), "hp");
+        #[cfg(feature = "intern-keys")]
+        assert!(std::sync::Arc::ptr_eq(&key("hp"), &key("hp")));
+    }
+}
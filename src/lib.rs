@@ -0,0 +1,11 @@
+pub mod async_proxy;
+pub mod builtins;
+pub mod cache;
+pub mod coerce;
+pub mod eval;
+pub mod fold;
+pub mod intern;
+pub mod parser;
+pub mod session;
+pub mod source;
+pub mod visit;
@@ -1,5 +1,3 @@
-mod eval;
-
 fn main() {
     println!("Hello, world!");
 }
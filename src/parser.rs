@@ -0,0 +1,909 @@
+use pest::error::{InputLocation, LineColLocation};
+use pest::iterators::Pair;
+use pest::pratt_parser::{Assoc, Op, PrattParser};
+use pest::Parser;
+use std::sync::OnceLock;
+use pest_derive::Parser;
+
+use crate::eval::{BinOp, CmpOp, Expression, Literal, Span, Spanned, TalkEvalError, TalkEvalErrorKind};
+
+#[derive(Parser)]
+#[grammar = "talk.pest"]
+struct TalkParser;
+
+/// The words the grammar's `keyword` rule reserves, which can't be identifiers or bare
+/// object keys. `talk.pest` can't refer to this, so `test_keywords` checks it lists the
+/// same ones.
+pub(crate) const KEYWORDS: &[&str] = &["true", "false", "null", "let", "in", "typeof", "xor"];
+
+/// How deeply `parse` and `parse_spanned` let source nest brackets, `?:` branches and
+/// assigned values. The grammar is parsed by recursive descent, which takes around 32 KB
+/// of native stack per level in a debug build and 8 KB in an optimized one, so this much
+/// fits the 2 MB a spawned thread gets by default.
+pub const MAX_PARSE_DEPTH: usize = 32;
+
+/// Parses a single Talk expression into its AST.
+pub fn parse(src: &str) -> Result<Expression, TalkEvalError> { parse_with(src, false, MAX_PARSE_DEPTH) }
+
+/// Like `parse`, but wraps each operator, index and call in an `Expression::Spanned` that
+/// records the source it came from, so that evaluation errors it raises point there.
+pub fn parse_spanned(src: &str) -> Result<Expression, TalkEvalError> { parse_with(src, true, MAX_PARSE_DEPTH) }
+
+/// Like `parse`, but lets source nest `max_depth` levels instead of `MAX_PARSE_DEPTH`. The
+/// stack that takes grows with it, at the rate `MAX_PARSE_DEPTH` gives.
+pub fn parse_with_limit(src: &str, max_depth: usize) -> Result<Expression, TalkEvalError> {
+    parse_with(src, false, max_depth)
+}
+
+/// `parse_spanned` with the nesting limit of `parse_with_limit`.
+pub fn parse_spanned_with_limit(src: &str, max_depth: usize) -> Result<Expression, TalkEvalError> {
+    parse_with(src, true, max_depth)
+}
+
+fn parse_with(src: &str, spans: bool, max_depth: usize) -> Result<Expression, TalkEvalError> {
+    check_nesting(src, max_depth)?;
+    let mut pairs = TalkParser::parse(Rule::program, src).map_err(|e| parse_error(src, e))?;
+    let program = pairs.next().unwrap();
+    let lines = if spans { Some(Lines::new(src)) } else { None };
+    build(program.into_inner().next().unwrap(), lines.as_ref())
+}
+
+/// Where each line of the source starts and which bytes continue a multi-byte character,
+/// so that a span finds its line and column without rescanning the source as
+/// `pest::Position::line_col` does.
+struct Lines {
+    starts: Vec<usize>,
+    continuations: Vec<usize>,
+}
+
+impl Lines {
+    fn new(src: &str) -> Self {
+        Lines {
+            starts: std::iter::once(0).chain(src.match_indices('\n').map(|(i, _)| i + 1)).collect(),
+            continuations: src.bytes().enumerate().filter(|&(_, b)| b & 0xC0 == 0x80).map(|(i, _)| i).collect(),
+        }
+    }
+
+    /// The span of the bytes `start..end`, on the line and character column of `start`.
+    fn span(&self, (start, end): (usize, usize)) -> Span {
+        let line = self.starts.partition_point(|&s| s <= start);
+        let line_start = self.starts[line - 1];
+        let wide = self.continuations.partition_point(|&i| i < start) - self.continuations.partition_point(|&i| i < line_start);
+        Span { start, end, line, col: start - line_start - wide + 1 }
+    }
+}
+
+fn parse_error(src: &str, e: pest::error::Error<Rule>) -> TalkEvalError {
+    let (start, end) = match e.location {
+        InputLocation::Pos(pos) => (pos, pos),
+        InputLocation::Span(span) => span,
+    };
+    let (line, col) = match e.line_col {
+        LineColLocation::Pos(pos) => pos,
+        LineColLocation::Span(pos, _) => pos,
+    };
+    let near = src[start..].lines().next().unwrap_or("");
+    let text = if near.is_empty() {
+        format!("Parse error at end of input: {}", e.variant.message())
+    } else {
+        format!("Parse error near {:?}: {}", near, e.variant.message())
+    };
+    TalkEvalError::from(TalkEvalErrorKind::ParseError(text)).with_span(Span { start, end, line, col })
+}
+
+/// Rejects source nesting deeper than `max_depth` with a cheap scan ahead of the parser.
+/// Anything it gets wrong beyond counting is left for the parser to report.
+fn check_nesting(src: &str, max_depth: usize) -> Result<(), TalkEvalError> {
+    // Per open bracket, how many `?` and `=` nest the current expression within it.
+    let mut frames = vec![0];
+    let mut depth = 0;
+    // Prefix operators in a row so far, each nesting the rest of the run within it.
+    let mut run = 0;
+    let bytes = src.as_bytes();
+    let word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'!' | b'-' | b'~' => run += 1,
+            b't' if src[i..].starts_with("typeof") && !bytes.get(i + 6).copied().is_some_and(word) && (i == 0 || !word(bytes[i - 1])) => {
+                run += 1;
+                i += 5;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' | b'#' => {}
+            _ => run = 0,
+        }
+        match bytes[i] {
+            b'#' => {
+                i = src[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'"' if src[i..].starts_with("\"\"\"") => {
+                i = src[i + 3..].find("\"\"\"").map_or(bytes.len(), |n| i + 3 + n + 3);
+                continue;
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'(' | b'{' | b'[' => {
+                frames.push(0);
+                depth += 1;
+            }
+            b')' | b'}' | b']' if frames.len() > 1 => depth -= frames.pop().unwrap() + 1,
+            b';' | b',' => depth -= std::mem::take(frames.last_mut().unwrap()),
+            b'=' if bytes.get(i + 1) == Some(&b'=') => i += 1,
+            b'?' if bytes.get(i + 1) == Some(&b'?') => i += 1,
+            b'?' if bytes.get(i + 1) == Some(&b'.') => {}
+            b'=' if i > 0 && b"=!<>".contains(&bytes[i - 1]) => {}
+            b'?' | b'=' => {
+                *frames.last_mut().unwrap() += 1;
+                depth += 1;
+            }
+            _ => {}
+        }
+        if depth + run > max_depth {
+            let (line, col) = pest::Position::new(src, i).unwrap().line_col();
+            let text = format!("Expression is nested deeper than {} levels", max_depth);
+            return Err(TalkEvalError::from(TalkEvalErrorKind::ParseError(text)).with_span(Span { start: i, end: i + 1, line, col }));
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+fn literal_error(pair: &Pair<Rule>, text: String) -> TalkEvalError {
+    TalkEvalError::from(TalkEvalErrorKind::ParseError(text)).with_span(span_of(pair.as_span()))
+}
+
+/// The `Span` of `span`, for errors; spans kept in the AST come from `Lines` instead.
+fn span_of(span: pest::Span) -> Span {
+    let (line, col) = span.start_pos().line_col();
+    Span { start: span.start(), end: span.end(), line, col }
+}
+
+/// The bytes a pair covers.
+fn bytes_of(pair: &Pair<Rule>) -> (usize, usize) { (pair.as_span().start(), pair.as_span().end()) }
+
+/// Operators from loosest to tightest binding. All binary operators associate to the left,
+/// so `a < b == c` compares `a < b` against `c`. `in` binds like a comparison, `xor`
+/// between `||` and `&&`, `??` just below `||`, and ranges loosest of all; the grammar keeps them from chaining. As in Rust,
+/// bitwise operators bind tighter than comparisons, so `flags & MASK == 0` needs no parentheses,
+/// and shifts tighter still, but looser than arithmetic.
+fn pratt() -> &'static PrattParser<Rule> {
+    static PRATT: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    PRATT.get_or_init(|| PrattParser::new()
+        .op(Op::infix(Rule::range_op, Assoc::Left))
+        .op(Op::infix(Rule::coalesce_op, Assoc::Left))
+        .op(Op::infix(Rule::or_op, Assoc::Left))
+        .op(Op::infix(Rule::xor_op, Assoc::Left))
+        .op(Op::infix(Rule::and_op, Assoc::Left))
+        .op(Op::infix(Rule::cmp_op, Assoc::Left) | Op::infix(Rule::in_op, Assoc::Left))
+        .op(Op::infix(Rule::bit_or_op, Assoc::Left))
+        .op(Op::infix(Rule::bit_xor_op, Assoc::Left))
+        .op(Op::infix(Rule::bit_and_op, Assoc::Left))
+        .op(Op::infix(Rule::shift_op, Assoc::Left))
+        .op(Op::infix(Rule::add_op, Assoc::Left))
+        .op(Op::infix(Rule::mul_op, Assoc::Left))
+        .op(Op::prefix(Rule::unary_op)))
+}
+
+/// `bytes_of` a `postfix`, without the whitespace it takes in after its last part while
+/// looking for another one.
+fn operand_bytes(pair: &Pair<Rule>) -> (usize, usize) {
+    let start = pair.as_span().start();
+    (start, start + pair.as_str().trim_end_matches([' ', '\t', '\r', '\n']).len())
+}
+
+/// The source from the start of `first` to the end of `last`.
+fn join(first: (usize, usize), last: (usize, usize)) -> (usize, usize) { (first.0, last.1) }
+
+/// `expr`, wrapped with the span of `bytes` if `spans` is set.
+fn spanned(expr: Expression, bytes: (usize, usize), spans: Option<&Lines>) -> Expression {
+    match spans {
+        Some(lines) => Expression::Spanned(Box::new(Spanned { node: expr, span: lines.span(bytes) })),
+        None => expr,
+    }
+}
+
+fn build(mut pair: Pair<Rule>, spans: Option<&Lines>) -> Result<Expression, TalkEvalError> {
+    // Going straight to what an `expression`, or a `conditional` without branches, wraps
+    // saves two frames per nesting level.
+    loop {
+        match pair.as_rule() {
+            Rule::expression => pair = pair.into_inner().next().unwrap(),
+            Rule::conditional if pair.clone().into_inner().nth(1).is_none() => pair = pair.into_inner().next().unwrap(),
+            _ => break,
+        }
+    }
+    match pair.as_rule() {
+        Rule::assignment => {
+            let mut inner = pair.into_inner();
+            let target = build_place(inner.next().unwrap());
+            let value = build(inner.next().unwrap(), spans)?;
+            Ok(Expression::Assign { target: Box::new(target), value: Box::new(value) })
+        }
+        Rule::local => build_local(pair, spans),
+        Rule::conditional => {
+            let mut inner = pair.into_inner();
+            let cond = build(inner.next().unwrap(), spans)?;
+            match (inner.next(), inner.next()) {
+                (Some(then_branch), Some(else_branch)) => Ok(Expression::Conditional {
+                    cond: Box::new(cond),
+                    then_branch: Box::new(build(then_branch, spans)?),
+                    else_branch: Box::new(build(else_branch, spans)?),
+                }),
+                _ => Ok(cond),
+            }
+        }
+        // Operands carry their spans up, so an operator's span covers both of them.
+        Rule::binary => pratt()
+            .map_primary(|p| {
+                let bytes = operand_bytes(&p);
+                Ok((build(p, spans)?, bytes))
+            })
+            .map_prefix(|op, operand| {
+                let (operand, operand_bytes) = operand?;
+                let (operand, span) = (Box::new(operand), join(bytes_of(&op), operand_bytes));
+                Ok(match op.as_str() {
+                    "!" => (Expression::Not { operand }, span),
+                    "-" => (spanned(Expression::Neg { operand }, span, spans), span),
+                    "~" => (spanned(Expression::BitNot { operand }, span, spans), span),
+                    _ => (Expression::TypeOf { operand }, span),
+                })
+            })
+            .map_infix(|lhs, op, rhs| {
+                let ((lhs, lhs_span), (rhs, rhs_span)) = (lhs?, rhs?);
+                let (lhs, rhs, span) = (Box::new(lhs), Box::new(rhs), join(lhs_span, rhs_span));
+                let expr = match op.as_rule() {
+                    Rule::range_op => spanned(Expression::Range { start: lhs, end: rhs, inclusive: op.as_str() == "..=" }, span, spans),
+                    Rule::coalesce_op => Expression::Coalesce { lhs, rhs },
+                    Rule::or_op => Expression::Or { lhs, rhs },
+                    Rule::xor_op => Expression::Xor { lhs, rhs },
+                    Rule::and_op => Expression::And { lhs, rhs },
+                    Rule::cmp_op => spanned(Expression::Compare { op: cmp_op(op.as_str()), lhs, rhs }, span, spans),
+                    Rule::in_op => spanned(Expression::In { needle: lhs, haystack: rhs }, span, spans),
+                    _ => spanned(Expression::Binary { op: bin_op(op.as_str()), lhs, rhs }, span, spans),
+                };
+                Ok((expr, span))
+            })
+            // `unary` stays a named rule so errors read "expected unary"; its prefix ops go to the parser as-is.
+            .parse(pair.into_inner().flat_map(|p| match p.as_rule() {
+                Rule::unary => p.into_inner().collect(),
+                _ => vec![p],
+            }))
+            .map(|(expr, _)| expr),
+        Rule::postfix => {
+            let start = bytes_of(&pair);
+            let mut inner = pair.into_inner();
+            let mut expr = build(inner.next().unwrap(), spans)?;
+            for part in inner {
+                expr = match part.as_rule() {
+                    Rule::optional_field => Expression::OptionalField {
+                        base: Box::new(expr),
+                        name: part.into_inner().next().unwrap().as_str().to_string(),
+                    },
+                    Rule::index => {
+                        let span = join(start, bytes_of(&part));
+                        let index = Expression::Index { base: Box::new(expr), index: Box::new(build(part.into_inner().next().unwrap(), spans)?) };
+                        spanned(index, span, spans)
+                    }
+                    _ => Expression::Field { base: Box::new(expr), name: part.as_str().to_string() },
+                };
+            }
+            Ok(expr)
+        }
+        Rule::call => {
+            let whole = pair.as_span();
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap().as_str().to_string();
+            let mut args: Vec<Expression> = inner.map(|p| build(p, spans)).collect::<Result<_, _>>()?;
+            if name != "defined" {
+                return Ok(spanned(Expression::Call { name, args }, (whole.start(), whole.end()), spans));
+            }
+            match args.pop() {
+                Some(path) if args.is_empty() && path.is_path() => Ok(Expression::Defined { path: Box::new(path) }),
+                _ => Err(TalkEvalError::from(TalkEvalErrorKind::ParseError("defined expects a field path, such as defined(a.b)".to_string())).with_span(span_of(whole))),
+            }
+        }
+        Rule::list => Ok(Expression::List(pair.into_inner().map(|p| build(p, spans)).collect::<Result<_, _>>()?)),
+        Rule::object => {
+            let mut entries: Vec<(String, Expression)> = Vec::new();
+            for entry in pair.into_inner() {
+                let mut inner = entry.into_inner();
+                let key = inner.next().unwrap();
+                let name = match key.as_rule() {
+                    Rule::string => build_string(key.clone())?,
+                    _ => key.as_str().to_string(),
+                };
+                if entries.iter().any(|(existing, _)| *existing == name) {
+                    return Err(literal_error(&key, format!("Duplicate key {} in object literal", name)));
+                }
+                entries.push((name, build(inner.next().unwrap(), spans)?));
+            }
+            Ok(Expression::Object(entries))
+        }
+        Rule::block => Ok(Expression::Block(pair.into_inner().map(|p| build(p, spans)).collect::<Result<_, _>>()?)),
+        Rule::identifier => Ok(Expression::Identifier(pair.as_str().to_string())),
+        Rule::int => parse_int(pair.as_str())
+            .map(|i| Expression::Literal(Literal::Int(i)))
+            .map_err(|_| literal_error(&pair, format!("Integer literal {} is out of range", pair.as_str()))),
+        Rule::float => pair.as_str().parse()
+            .map(|x| Expression::Literal(Literal::Float(x)))
+            .map_err(|_| literal_error(&pair, format!("Invalid float literal {}", pair.as_str()))),
+        Rule::string => Ok(Expression::Literal(Literal::String(build_string(pair)?))),
+        Rule::raw_string => {
+            let inner = pair.into_inner().next().unwrap();
+            Ok(Expression::Literal(Literal::String(trim_indent(inner.as_str()))))
+        }
+        Rule::boolean => Ok(Expression::Literal(Literal::Bool(pair.as_str() == "true"))),
+        Rule::null => Ok(Expression::Literal(Literal::Null)),
+        rule => unreachable!("unexpected rule {:?}", rule),
+    }
+}
+
+/// Parses a decimal literal or one prefixed with `0x`, `0b` or `0o`.
+fn parse_int(text: &str) -> Result<i64, std::num::ParseIntError> {
+    let (digits, radix) = match text.get(..2) {
+        Some("0x") => (&text[2..], 16),
+        Some("0b") => (&text[2..], 2),
+        Some("0o") => (&text[2..], 8),
+        _ => (text, 10),
+    };
+    i64::from_str_radix(digits, radix)
+}
+
+fn build_string(pair: Pair<Rule>) -> Result<String, TalkEvalError> {
+    let mut text = String::new();
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::escape => text.push(unescape(&part)?),
+            _ => text.push_str(part.as_str()),
+        }
+    }
+    Ok(text)
+}
+
+/// Decodes one `escape` pair: `\n`, `\t`, `\r`, `\"`, `\\`, `\0` or `\u{XXXX}` with 1 to 6 hex digits.
+fn unescape(pair: &Pair<Rule>) -> Result<char, TalkEvalError> {
+    let escape = pair.as_str();
+    let decoded = match &escape[1..] {
+        "n" => Some('\n'),
+        "t" => Some('\t'),
+        "r" => Some('\r'),
+        "\"" => Some('"'),
+        "\\" => Some('\\'),
+        "0" => Some('\0'),
+        code if code.starts_with("u{") => {
+            let hex = &code[2..code.len() - 1];
+            if hex.is_empty() || hex.len() > 6 {
+                None
+            } else {
+                u32::from_str_radix(hex, 16).ok().and_then(std::char::from_u32)
+            }
+        }
+        _ => None,
+    };
+    decoded.ok_or_else(|| literal_error(pair, format!("Invalid escape {} in string literal", escape)))
+}
+
+/// Lays out a `"""` block so it can be indented along with the surrounding script:
+/// - a line break right after the opening quotes is dropped, as is whitespace before the closing ones;
+/// - the indentation shared by all non-blank lines is removed;
+/// - everything else, including quotes, backslashes and blank lines, is kept verbatim.
+fn trim_indent(raw: &str) -> String {
+    let raw = raw.strip_prefix("\r\n").or_else(|| raw.strip_prefix('\n')).unwrap_or(raw);
+    let raw = raw.trim_end_matches([' ', '\t']);
+    let indent = raw.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    raw.split('\n')
+        .map(|line| line.get(indent..).unwrap_or_else(|| line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Out of line, as every arm `build` inlines adds to its frame, and nesting stacks them.
+#[inline(never)]
+fn build_local(pair: Pair<Rule>, spans: Option<&Lines>) -> Result<Expression, TalkEvalError> {
+    // Past `let_kw`.
+    let mut inner = pair.into_inner().skip(1);
+    let name = inner.next().unwrap().as_str().to_string();
+    let value = build(inner.next().unwrap(), spans)?;
+    Ok(Expression::Let { name, value: Box::new(value) })
+}
+
+fn build_place(pair: Pair<Rule>) -> Expression {
+    let mut names = pair.into_inner();
+    let mut expr = Expression::Identifier(names.next().unwrap().as_str().to_string());
+    for name in names {
+        expr = Expression::Field { base: Box::new(expr), name: name.as_str().to_string() };
+    }
+    expr
+}
+
+fn bin_op(op: &str) -> BinOp {
+    match op {
+        "+" => BinOp::Add,
+        "-" => BinOp::Sub,
+        "*" => BinOp::Mul,
+        "/" => BinOp::Div,
+        "&" => BinOp::BitAnd,
+        "|" => BinOp::BitOr,
+        "^" => BinOp::BitXor,
+        "<<" => BinOp::Shl,
+        ">>" => BinOp::Shr,
+        _ => BinOp::Mod,
+    }
+}
+
+fn cmp_op(op: &str) -> CmpOp {
+    match op {
+        "==" => CmpOp::Eq,
+        "!=" => CmpOp::Ne,
+        "<" => CmpOp::Lt,
+        "<=" => CmpOp::Le,
+        ">" => CmpOp::Gt,
+        _ => CmpOp::Ge,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{eval_str, BinOp, CmpOp, Expression, Literal, Span, Spanned, TalkObject, TalkValue};
+    use crate::fold::fold_constants;
+    use crate::parser::{parse, parse_spanned, parse_with_limit, KEYWORDS, MAX_PARSE_DEPTH};
+
+    fn int(i: i64) -> Box<Expression> { Box::new(Expression::Literal(Literal::Int(i))) }
+
+    fn ident(name: &str) -> Box<Expression> { Box::new(Expression::Identifier(name.to_string())) }
+
+    fn string(s: &str) -> Expression { Expression::Literal(Literal::String(s.to_string())) }
+
+    fn binary(op: BinOp, lhs: Box<Expression>, rhs: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Binary { op, lhs, rhs })
+    }
+
+    #[test]
+    fn test_parse_arithmetic() {
+        assert_eq!(parse("1 + 2 * 3").unwrap(), *binary(BinOp::Add, int(1), binary(BinOp::Mul, int(2), int(3))));
+        assert_eq!(parse("(1 + 2) * 3").unwrap(), *binary(BinOp::Mul, binary(BinOp::Add, int(1), int(2)), int(3)));
+        assert_eq!(parse("7 - 2 - 1").unwrap(), *binary(BinOp::Sub, binary(BinOp::Sub, int(7), int(2)), int(1)));
+        assert_eq!(parse("-x % 2").unwrap(), *binary(BinOp::Mod, Box::new(Expression::Neg { operand: ident("x") }), int(2)));
+    }
+
+    #[test]
+    fn test_parse_bitwise() {
+        assert_eq!(parse("a | b ^ c & d").unwrap(), *binary(BinOp::BitOr, ident("a"), binary(BinOp::BitXor, ident("b"), binary(BinOp::BitAnd, ident("c"), ident("d")))));
+        assert_eq!(parse("flags & MASK == 0").unwrap(), Expression::Compare {
+            op: CmpOp::Eq,
+            lhs: binary(BinOp::BitAnd, ident("flags"), ident("MASK")),
+            rhs: int(0),
+        });
+        assert_eq!(parse("a & b && c | d || e").unwrap(), Expression::Or {
+            lhs: Box::new(Expression::And { lhs: binary(BinOp::BitAnd, ident("a"), ident("b")), rhs: binary(BinOp::BitOr, ident("c"), ident("d")) }),
+            rhs: ident("e"),
+        });
+        assert_eq!(parse("~x & 1 + 2").unwrap(), *binary(BinOp::BitAnd, Box::new(Expression::BitNot { operand: ident("x") }), binary(BinOp::Add, int(1), int(2))));
+        assert!(parse("a & & b").is_err());
+
+        assert_eq!(parse("1 << n + 1 & m").unwrap(), *binary(BinOp::BitAnd, binary(BinOp::Shl, int(1), binary(BinOp::Add, ident("n"), int(1))), ident("m")));
+        assert_eq!(parse("a >> 1 < b << 1").unwrap(), Expression::Compare {
+            op: CmpOp::Lt,
+            lhs: binary(BinOp::Shr, ident("a"), int(1)),
+            rhs: binary(BinOp::Shl, ident("b"), int(1)),
+        });
+        assert_eq!(parse("a<=b").unwrap(), Expression::Compare { op: CmpOp::Le, lhs: ident("a"), rhs: ident("b") });
+        assert!(parse("a <<< b").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut ctx = TalkObject::new();
+        let mut eval = |src: &str| eval_str(src, &mut ctx).unwrap();
+        assert_eq!(eval("2 + 3 * 4"), TalkValue::Int(14));
+        assert_eq!(eval("(2 + 3) * 4"), TalkValue::Int(20));
+        assert_eq!(eval("10 - 4 - 3"), TalkValue::Int(3));
+        assert_eq!(eval("10 - (4 - 3)"), TalkValue::Int(9));
+        assert_eq!(eval("100 / 10 / 5"), TalkValue::Int(2));
+        assert_eq!(eval("-2 * 3 + 7 % 4"), TalkValue::Int(-3));
+        assert_eq!(eval("-(2 + 3)"), TalkValue::Int(-5));
+        assert_eq!(eval("1 + 2 < 2 * 2"), TalkValue::Bool(true));
+        assert_eq!(eval("1 < 2 == true"), TalkValue::Bool(true));
+        assert_eq!(eval("true || false && false"), TalkValue::Bool(true));
+        assert_eq!(eval("(true || false) && false"), TalkValue::Bool(false));
+        assert_eq!(eval("!false && 1 + 1 == 2"), TalkValue::Bool(true));
+        assert_eq!(eval("1 + 1 == 2 ? 2 * 5 : 0"), TalkValue::Int(10));
+    }
+
+    #[test]
+    fn test_parse_xor() {
+        let xor = |lhs: Box<Expression>, rhs: Box<Expression>| Box::new(Expression::Xor { lhs, rhs });
+        assert_eq!(parse("a || b xor c && d").unwrap(), Expression::Or {
+            lhs: ident("a"),
+            rhs: xor(ident("b"), Box::new(Expression::And { lhs: ident("c"), rhs: ident("d") })),
+        });
+        assert_eq!(parse("a xor b xor c").unwrap(), *xor(xor(ident("a"), ident("b")), ident("c")));
+        assert_eq!(parse("xorz").unwrap(), *ident("xorz"));
+        assert!(parse("a xorb").is_err());
+    }
+
+    #[test]
+    fn test_parse_logic() {
+        let expected = Expression::Or {
+            lhs: Box::new(Expression::And { lhs: ident("a"), rhs: ident("b") }),
+            rhs: Box::new(Expression::Compare { op: CmpOp::Le, lhs: ident("c"), rhs: int(1) }),
+        };
+        assert_eq!(parse("a && b || c <= 1").unwrap(), expected);
+
+        let expected = Expression::Conditional {
+            cond: Box::new(Expression::Not { operand: ident("friendly") }),
+            then_branch: Box::new(Expression::Literal(Literal::String("Go away".to_string()))),
+            else_branch: Box::new(Expression::Literal(Literal::Float(1.5))),
+        };
+        assert_eq!(parse("!friendly ? \"Go away\" : 1.5").unwrap(), expected);
+        assert_eq!(parse("null != true").unwrap(), Expression::Compare {
+            op: CmpOp::Ne,
+            lhs: Box::new(Expression::Literal(Literal::Null)),
+            rhs: Box::new(Expression::Literal(Literal::Bool(true))),
+        });
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        assert_eq!(parse("true").unwrap(), Expression::Literal(Literal::Bool(true)));
+        assert_eq!(parse("false").unwrap(), Expression::Literal(Literal::Bool(false)));
+        assert_eq!(parse("\"true\"").unwrap(), Expression::Literal(Literal::String("true".to_string())));
+        // Keywords only match as whole words.
+        assert_eq!(parse("falsey").unwrap(), *ident("falsey"));
+        assert_eq!(parse("!false").unwrap(), Expression::Not { operand: Box::new(Expression::Literal(Literal::Bool(false))) });
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        let target = Box::new(Expression::Field { base: ident("player"), name: "score".to_string() });
+        let value = Box::new(Expression::Field { base: ident("quest"), name: "reward".to_string() });
+        assert_eq!(parse("player.score = quest.reward").unwrap(), Expression::Assign { target, value });
+        assert_eq!(parse("trueish = nullable").unwrap(), Expression::Assign { target: ident("trueish"), value: ident("nullable") });
+
+        // `let` is reserved, so it can't be a name itself.
+        assert_eq!(parse("let x = 1").unwrap(), Expression::Let { name: "x".to_string(), value: int(1) });
+        assert_eq!(parse("letter = 1").unwrap(), Expression::Assign { target: ident("letter"), value: int(1) });
+        assert!(parse("let = 1").is_err());
+        assert!(parse("let.x").is_err());
+        assert!(parse("let a.b = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_call() {
+        assert_eq!(parse("f()").unwrap(), Expression::Call { name: "f".to_string(), args: vec![] });
+        assert_eq!(parse("max(a, 1 + 2).b").unwrap(), Expression::Field {
+            base: Box::new(Expression::Call {
+                name: "max".to_string(),
+                args: vec![*ident("a"), *binary(BinOp::Add, int(1), int(2))],
+            }),
+            name: "b".to_string(),
+        });
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let index = |base: Box<Expression>, index: Box<Expression>| Box::new(Expression::Index { base, index });
+        assert_eq!(parse("items[i + 1]").unwrap(), *index(ident("items"), binary(BinOp::Add, ident("i"), int(1))));
+        assert_eq!(parse("a.b[0].c").unwrap(), Expression::Field {
+            base: index(Box::new(Expression::Field { base: ident("a"), name: "b".to_string() }), int(0)),
+            name: "c".to_string(),
+        });
+        assert_eq!(parse("grid[1][2]").unwrap(), *index(index(ident("grid"), int(1)), int(2)));
+        assert_eq!(parse("-v[0]").unwrap(), Expression::Neg { operand: index(ident("v"), int(0)) });
+        assert!(parse("items[]").is_err());
+    }
+
+    #[test]
+    fn test_parse_in() {
+        let is_in = |needle: Box<Expression>, haystack: Box<Expression>| Box::new(Expression::In { needle, haystack });
+        assert_eq!(parse("key in bag").unwrap(), *is_in(ident("key"), ident("bag")));
+        assert_eq!(parse("a + 1 in b && c").unwrap(), Expression::And {
+            lhs: is_in(binary(BinOp::Add, ident("a"), int(1)), ident("b")),
+            rhs: ident("c"),
+        });
+        assert_eq!(parse("a in b == false").unwrap(), Expression::Compare {
+            op: CmpOp::Eq,
+            lhs: is_in(ident("a"), ident("b")),
+            rhs: Box::new(Expression::Literal(Literal::Bool(false))),
+        });
+        assert_eq!(parse("index").unwrap(), *ident("index"));
+        assert!(parse("in = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = |start: Box<Expression>, end: Box<Expression>, inclusive: bool| Expression::Range { start, end, inclusive };
+        assert_eq!(parse("1..10").unwrap(), range(int(1), int(10), false));
+        assert_eq!(parse("0..=n - 1").unwrap(), range(int(0), binary(BinOp::Sub, ident("n"), int(1)), true));
+        assert_eq!(parse("a.b..c").unwrap(), range(Box::new(Expression::Field { base: ident("a"), name: "b".to_string() }), ident("c"), false));
+        assert_eq!(parse("1.5").unwrap(), Expression::Literal(Literal::Float(1.5)));
+        assert!(parse("1..2..3").is_err());
+        assert!(parse("(1..2)..3").is_ok());
+        assert!(parse("1..").is_err());
+    }
+
+    #[test]
+    fn test_parse_coalesce() {
+        let coalesce = |lhs: Box<Expression>, rhs: Box<Expression>| Box::new(Expression::Coalesce { lhs, rhs });
+        assert_eq!(parse("a ?? b ?? c").unwrap(), *coalesce(coalesce(ident("a"), ident("b")), ident("c")));
+        assert_eq!(parse("a || b ?? c").unwrap(), *coalesce(Box::new(Expression::Or { lhs: ident("a"), rhs: ident("b") }), ident("c")));
+        assert_eq!(parse("a ?? b ? 1 : 2").unwrap(), Expression::Conditional {
+            cond: coalesce(ident("a"), ident("b")),
+            then_branch: int(1),
+            else_branch: int(2),
+        });
+        assert!(parse("a ??").is_err());
+    }
+
+    #[test]
+    fn test_parse_optional_field() {
+        let optional = |base: Box<Expression>, name: &str| Box::new(Expression::OptionalField { base, name: name.to_string() });
+        assert_eq!(parse("player?.quest?.stage").unwrap(), *optional(optional(ident("player"), "quest"), "stage"));
+        assert_eq!(parse("a?.b.c").unwrap(), Expression::Field { base: optional(ident("a"), "b"), name: "c".to_string() });
+        assert_eq!(parse("a ? b?.c : d").unwrap(), Expression::Conditional {
+            cond: ident("a"),
+            then_branch: optional(ident("b"), "c"),
+            else_branch: ident("d"),
+        });
+        assert!(parse("a?.b = 1").is_err());
+        assert!(parse("a?.").is_err());
+    }
+
+    #[test]
+    fn test_parse_typeof() {
+        let type_of = |operand: Box<Expression>| Box::new(Expression::TypeOf { operand });
+        assert_eq!(parse("typeof v == \"object\"").unwrap(), Expression::Compare {
+            op: CmpOp::Eq,
+            lhs: type_of(ident("v")),
+            rhs: Box::new(string("object")),
+        });
+        assert_eq!(parse("typeof a.b").unwrap(), *type_of(Box::new(Expression::Field { base: ident("a"), name: "b".to_string() })));
+        assert_eq!(parse("!typeof(x)").unwrap(), Expression::Not { operand: type_of(ident("x")) });
+        assert_eq!(parse("typeofx").unwrap(), *ident("typeofx"));
+        assert!(parse("typeof").is_err());
+        assert!(parse("typeof = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_defined() {
+        let defined = |path: Box<Expression>| Expression::Defined { path };
+        assert_eq!(parse("defined(foo)").unwrap(), defined(ident("foo")));
+        assert_eq!(parse("defined(a.b) && x").unwrap(), Expression::And {
+            lhs: Box::new(defined(Box::new(Expression::Field { base: ident("a"), name: "b".to_string() }))),
+            rhs: ident("x"),
+        });
+        let err = parse("x = defined(1 + 1)").unwrap_err();
+        assert_eq!(err.text(), "defined expects a field path, such as defined(a.b)");
+        assert_eq!(err.span, Some(Span { start: 4, end: 18, line: 1, col: 5 }));
+        assert!(parse("defined()").is_err());
+        assert!(parse("defined(a, b)").is_err());
+        assert!(parse("defined(f().a)").is_err());
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let commented = "# greet the player\nfriendly # mood check\n  ? \"Hi #1\" # kind\n  : \"Go away\" #";
+        assert_eq!(parse(commented).unwrap(), parse("friendly ? \"Hi #1\" : \"Go away\"").unwrap());
+        assert_eq!(parse("x = 1 + #\n2").unwrap(), parse("x = 1 + 2").unwrap());
+        assert!(parse("# only a comment").unwrap_err().text().starts_with("Parse error at end of input"));
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        assert_eq!(parse(r#""line1\nline2""#).unwrap(), string("line1\nline2"));
+        assert_eq!(parse(r#""a\tb\r""#).unwrap(), string("a\tb\r"));
+        assert_eq!(parse(r#""say \"hi\"""#).unwrap(), string("say \"hi\""));
+        assert_eq!(parse(r#""C:\\temp""#).unwrap(), string("C:\\temp"));
+        assert_eq!(parse(r#""nul\0""#).unwrap(), string("nul\0"));
+        assert_eq!(parse(r#""\u{48}i \u{1F600}""#).unwrap(), string("Hi \u{1F600}"));
+        assert_eq!(parse(r#""""#).unwrap(), string(""));
+    }
+
+    #[test]
+    fn test_parse_escape_errors() {
+        let err = parse(r#"greeting = "oops\q""#).unwrap_err();
+        assert_eq!(err.text(), "Invalid escape \\q in string literal");
+        assert_eq!(err.span, Some(Span { start: 16, end: 18, line: 1, col: 17 }));
+
+        assert_eq!(parse(r#""\u{D800}""#).unwrap_err().text(), "Invalid escape \\u{D800} in string literal");
+        assert_eq!(parse(r#""\u{}""#).unwrap_err().text(), "Invalid escape \\u{} in string literal");
+        assert_eq!(parse(r#""\u{1234567}""#).unwrap_err().text(), "Invalid escape \\u{1234567} in string literal");
+        assert!(parse(r#""\u{zz}""#).is_err());
+        assert!(parse(r#""unterminated\""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_raw_strings() {
+        let src = "line = \"\"\"\n    \"Halt!\" said the guard.\n\n      He didn't move: \\n is just text.\n    \"\"\"";
+        let value = string("\"Halt!\" said the guard.\n\n  He didn't move: \\n is just text.\n");
+        assert_eq!(parse(src).unwrap(), Expression::Assign { target: ident("line"), value: Box::new(value) });
+
+        assert_eq!(parse(r#""""She said "no".""""#).unwrap(), string("She said \"no\"."));
+        assert_eq!(parse("\"\"\"\"\"\"").unwrap(), string(""));
+        assert_eq!(parse("\"\"\"# not a comment\"\"\"").unwrap(), string("# not a comment"));
+        assert!(parse("\"\"\"never closed\"\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_radix_ints() {
+        assert_eq!(parse("0xFF").unwrap(), *int(255));
+        assert_eq!(parse("0x7fffffffffffffff").unwrap(), *int(i64::MAX));
+        assert_eq!(parse("0b1010").unwrap(), *int(10));
+        assert_eq!(parse("0o17").unwrap(), *int(15));
+        assert_eq!(parse("017").unwrap(), *int(17));
+        assert_eq!(parse("flags % 0x10").unwrap(), *binary(BinOp::Mod, ident("flags"), int(16)));
+
+        assert_eq!(parse("0x8000000000000000").unwrap_err().text(), "Integer literal 0x8000000000000000 is out of range");
+        let wide = format!("0b1{}", "0".repeat(63));
+        assert_eq!(parse(&wide).unwrap_err().text(), format!("Integer literal {} is out of range", wide));
+        assert!(parse("0b102").is_err());
+        assert!(parse("0x").is_err());
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let assign = |name: &str, value: i64| Expression::Assign { target: ident(name), value: int(value) };
+        assert_eq!(parse("{ x = 1; y = 2; x + y }").unwrap(), Expression::Block(vec![
+            assign("x", 1),
+            assign("y", 2),
+            *binary(BinOp::Add, ident("x"), ident("y")),
+        ]));
+        assert_eq!(parse("{ x }").unwrap(), Expression::Block(vec![*ident("x")]));
+        assert_eq!(parse("{ a ? b : c }").unwrap(), Expression::Block(vec![Expression::Conditional {
+            cond: ident("a"),
+            then_branch: ident("b"),
+            else_branch: ident("c"),
+        }]));
+        assert_eq!(parse("{ 1 } * 2").unwrap(), *binary(BinOp::Mul, Box::new(Expression::Block(vec![*int(1)])), int(2)));
+        assert!(parse("{ x = 1; }").is_err());
+
+        let local = Expression::Let { name: "x".to_string(), value: int(1) };
+        assert_eq!(parse("{ let x = 1; x }").unwrap(), Expression::Block(vec![local, *ident("x")]));
+    }
+
+    #[test]
+    fn test_parse_nesting_limit() {
+        let nested = |depth: usize| "(".repeat(depth) + "1" + &")".repeat(depth);
+        assert_eq!(parse(&nested(MAX_PARSE_DEPTH)).unwrap(), *int(1));
+        let err = parse(&nested(5_000)).unwrap_err();
+        assert_eq!(err.text(), "Expression is nested deeper than 32 levels");
+        assert_eq!(err.span.unwrap().start, MAX_PARSE_DEPTH);
+        assert_eq!(parse_with_limit(&nested(48), 48).unwrap(), *int(1));
+        assert_eq!(parse_with_limit(&nested(11), 10).unwrap_err().text(), "Expression is nested deeper than 10 levels");
+
+        let branches = |depth: usize| "a ? b : ".repeat(depth) + "1";
+        assert!(parse(&branches(MAX_PARSE_DEPTH)).is_ok());
+        assert!(parse(&branches(MAX_PARSE_DEPTH + 1)).is_err());
+        assert!(parse(&("x = ".repeat(MAX_PARSE_DEPTH + 1) + "1")).is_err());
+        let blocks = "{ ".repeat(MAX_PARSE_DEPTH - 1) + &"x = 1; ".repeat(100) + "x" + &" }".repeat(MAX_PARSE_DEPTH - 1);
+        assert!(parse(&blocks).is_ok());
+
+        // Brackets inside strings and comments don't count, nor do comparisons.
+        let quoted = "\"".to_string() + &"(".repeat(100) + "\\\"\" == \"\"\"" + &"{".repeat(100) + "\"\"\" # " + &"(".repeat(100);
+        assert!(parse(&quoted).is_ok());
+        assert!(parse(&vec!["1 <= 2"; 100].join(" == ")).is_ok());
+        assert!(parse(&vec!["x"; 100].join(" ?? ")).is_ok());
+        assert!(parse(&vec!["x"; 100].join("?.")).is_ok());
+
+        // Each prefix operator in a run nests the rest within it.
+        for op in ["-", "!", "~", "typeof "] {
+            assert!(parse(&(op.repeat(MAX_PARSE_DEPTH) + "1")).is_ok());
+            let err = parse(&(op.repeat(10_000) + "1")).unwrap_err();
+            assert_eq!(err.text(), "Expression is nested deeper than 32 levels");
+        }
+        assert_eq!(parse(&("!".repeat(10_000) + "1")).unwrap_err().span.unwrap().start, MAX_PARSE_DEPTH);
+        assert!(parse(&vec!["x"; 100].join(" - ")).is_ok());
+        assert!(parse(&vec!["typeofs"; 100].join(" + ")).is_ok());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let list = |items: Vec<Expression>| Expression::List(items);
+        assert_eq!(parse("[]").unwrap(), list(vec![]));
+        assert_eq!(parse("[1, \"two\", true]").unwrap(), list(vec![*int(1), string("two"), Expression::Literal(Literal::Bool(true))]));
+        assert_eq!(parse("[1, 2,]").unwrap(), list(vec![*int(1), *int(2)]));
+        assert_eq!(parse("[[], [x + 1]]").unwrap(), list(vec![list(vec![]), list(vec![*binary(BinOp::Add, ident("x"), int(1))])]));
+        assert!(parse("[,]").is_err());
+        assert!(parse("[1 2]").is_err());
+        assert!(parse(&("[".repeat(100) + &"]".repeat(100))).is_err());
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let entry = |key: &str, value: Expression| (key.to_string(), value);
+        assert_eq!(parse("{}").unwrap(), Expression::Object(vec![]));
+        assert_eq!(parse("{ name: \"Bob\", \"max hp\": 100, }").unwrap(), Expression::Object(vec![
+            entry("name", string("Bob")),
+            entry("max hp", *int(100)),
+        ]));
+        assert_eq!(parse("{ pos: { x: 1 } }").unwrap(), Expression::Object(vec![
+            entry("pos", Expression::Object(vec![entry("x", *int(1))])),
+        ]));
+
+        let err = parse("{ hp: 1, \"hp\": 2 }").unwrap_err();
+        assert_eq!(err.text(), "Duplicate key hp in object literal");
+        assert_eq!(err.span, Some(Span { start: 9, end: 13, line: 1, col: 10 }));
+        assert!(parse("{ true: 1 }").is_err());
+        assert!(parse("{ a: 1; b: 2 }").is_err());
+    }
+
+    #[test]
+    fn test_keywords() {
+        let grammar = include_str!("talk.pest");
+        let rule = grammar.lines().find(|line| line.starts_with("keyword ")).unwrap();
+        let listed: Vec<&str> = rule.split('"').skip(1).step_by(2).collect();
+        assert_eq!(listed, KEYWORDS);
+        for keyword in KEYWORDS {
+            assert!(parse(&format!("{} = 1", keyword)).is_err(), "{} parsed as a name", keyword);
+            assert!(parse(&format!("{{ {}: 1 }}", keyword)).is_err(), "{} parsed as a key", keyword);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("1 + * 2").unwrap_err().text(), "Parse error near \"* 2\": expected unary");
+        assert!(parse("1 +").unwrap_err().text().starts_with("Parse error at end of input"));
+        assert!(parse("a = ").is_err());
+        assert_eq!(parse("99999999999999999999").unwrap_err().text(), "Integer literal 99999999999999999999 is out of range");
+    }
+
+    #[test]
+    fn test_parse_error_spans() {
+        let err = parse("1 +\n  (2 * )").unwrap_err();
+        assert_eq!(err.span, Some(Span { start: 11, end: 11, line: 2, col: 8 }));
+        assert_eq!(format!("{:?}", err), "error at 2:8: Parse error near \")\": expected unary");
+
+        let err = parse("x = 1 + 99999999999999999999").unwrap_err();
+        assert_eq!(err.span, Some(Span { start: 8, end: 28, line: 1, col: 9 }));
+    }
+
+    #[test]
+    fn test_parse_spanned() {
+        let spanned = |node: Expression, start: usize, end: usize| {
+            Box::new(Expression::Spanned(Box::new(Spanned { node, span: Span { start, end, line: 1, col: start + 1 } })))
+        };
+        let index = spanned(Expression::Index { base: ident("a"), index: int(0) }, 4, 8);
+        let sum = spanned(Expression::Binary { op: BinOp::Add, lhs: ident("x"), rhs: index }, 0, 8);
+        let less = spanned(Expression::Compare { op: CmpOp::Lt, lhs: sum, rhs: int(2) }, 0, 13);
+        assert_eq!(parse_spanned("x + a[0]  < 2 || !y").unwrap(), Expression::Or { lhs: less, rhs: Box::new(Expression::Not { operand: ident("y") }) });
+
+        // Spans render as nothing and fold away with the nodes they cover.
+        let src = "-(1 + 2) * f(x.y, len([1]))";
+        assert_eq!(parse_spanned(src).unwrap().to_source(), src);
+        assert_eq!(parse(&parse_spanned(src).unwrap().to_source()).unwrap(), parse(src).unwrap());
+        assert_eq!(fold_constants(parse_spanned("1 + 2 * 3").unwrap()), *int(7));
+    }
+
+    #[test]
+    fn test_parse_large() {
+        // Spans take time linear in the source, not in the source per node. The sums nest
+        // 20k deep on the left, which is more than a test thread's stack will drop.
+        std::thread::Builder::new().stack_size(64 << 20).spawn(|| {
+            for term in ["1", "a[0]", "f(x)"] {
+                let src = vec![term; 20_000].join(" + ");
+                let start = std::time::Instant::now();
+                assert!(parse(&src).is_ok());
+                assert!(parse_spanned(&src).is_ok());
+                assert!(start.elapsed() < std::time::Duration::from_secs(10), "{} took {:?}", term, start.elapsed());
+            }
+        }).unwrap().join().unwrap();
+
+        // And agree with pest's own line and column, across lines and wide characters.
+        let src = "{\n  \"é\" + a[0];\r\n  \"日本\" + b }";
+        let lines = super::Lines::new(src);
+        for (i, _) in src.char_indices() {
+            let (line, col) = pest::Position::new(src, i).unwrap().line_col();
+            assert_eq!((lines.span((i, i)).line, lines.span((i, i)).col), (line, col), "at {}", i);
+        }
+        assert_eq!(lines.span((11, 15)), Span { start: 11, end: 15, line: 2, col: 9 });
+    }
+}
@@ -0,0 +1,67 @@
+//! Line-at-a-time evaluation against a context that outlives each line, for REPLs and
+//! debug consoles.
+
+use crate::cache::ProgramCache;
+use crate::eval::{TalkEvalError, TalkObject, TalkValue};
+
+#[derive(Debug, Default)]
+pub struct Session<'world> {
+    context: TalkObject<'world>,
+    programs: ProgramCache,
+}
+
+impl<'world> Session<'world> {
+    pub fn new() -> Self { Default::default() }
+
+    /// Starts a session on an existing context, such as one with proxies or functions registered.
+    pub fn with_context(context: TalkObject<'world>) -> Self { Self { context, programs: Default::default() } }
+
+    /// Parses and evaluates one line. Assignments persist for the following lines, and
+    /// repeated lines are parsed once; see `ProgramCache`.
+    /// A line with nothing but whitespace or comments evaluates to `Null`.
+    pub fn eval_line(&mut self, src: &str) -> Result<TalkValue<'world>, TalkEvalError> {
+        if is_blank(src) {
+            return Ok(TalkValue::Null);
+        }
+        self.programs.eval_str(src, &mut self.context)
+    }
+
+    pub fn programs(&mut self) -> &mut ProgramCache { &mut self.programs }
+
+    pub fn context(&self) -> &TalkObject<'world> { &self.context }
+
+    pub fn context_mut(&mut self) -> &mut TalkObject<'world> { &mut self.context }
+
+    pub fn into_context(self) -> TalkObject<'world> { self.context }
+}
+
+fn is_blank(src: &str) -> bool {
+    src.lines().all(|line| {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{TalkEvalError, TalkObjectProxy, TalkValue};
+    use crate::session::Session;
+
+    #[test]
+    fn test_eval_line() -> Result<(), TalkEvalError> {
+        let mut session = Session::new();
+        assert_eq!(session.eval_line("x = 5")?, TalkValue::Int(5));
+        assert_eq!(session.eval_line("x + 1")?, TalkValue::Int(6));
+        assert_eq!(session.eval_line("x = x + 1")?, TalkValue::Int(6));
+        assert_eq!(session.eval_line("x = x + 1")?, TalkValue::Int(7));
+        assert_eq!(session.programs().len(), 3);
+        assert_eq!(session.eval_line("  # just a note")?, TalkValue::Null);
+        assert_eq!(session.eval_line("")?, TalkValue::Null);
+
+        // A failing line leaves earlier state in place.
+        assert!(session.eval_line("x = ").is_err());
+        assert_eq!(session.eval_line("x")?, TalkValue::Int(7));
+        assert_eq!(*session.into_context().get("x").unwrap(), TalkValue::Int(7));
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! Renders an `Expression` back to Talk source.
+//!
+//! Parentheses are only emitted where precedence requires them, so the output is canonical:
+//! `parse(&expr.to_source())` gives back `expr` for any tree the parser builds. Comments,
+//! layout and redundant parentheses are not part of the AST and don't survive.
+//!
+//! Trees built by hand can hold nodes that have no Talk syntax. `If`, `Match`, `While` and
+//! `ForIn` render in a readable pseudo-syntax that doesn't parse, and negative number
+//! literals render as a negation, which parses to `Neg` of a literal. An empty `Block`
+//! renders as `{ null }`, since `{}` is an empty object. The spans `parse_spanned` adds
+//! aren't source either: a `Spanned` node renders as the node it wraps.
+
+use std::fmt::Write;
+
+use crate::eval::{BinOp, Expression, Literal};
+use crate::parser::KEYWORDS;
+
+/// Binding strength, loosest first. A child binding more loosely than its position
+/// allows is parenthesized.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Prec {
+    Assign,
+    Conditional,
+    Range,
+    Coalesce,
+    Or,
+    Xor,
+    And,
+    Compare,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Sum,
+    Term,
+    Unary,
+    Postfix,
+}
+
+impl Expression {
+    /// Formats the expression as Talk source; see the `source` module. `If`, `Match`,
+    /// `While` and `ForIn` have no Talk syntax, so a tree holding any of them formats to
+    /// text that doesn't parse; every other tree round-trips through `parse`.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        write_expr(self, &mut out);
+        out
+    }
+}
+
+fn prec(expr: &Expression) -> Prec {
+    match expr {
+        Expression::Spanned(spanned) => prec(&spanned.node),
+        Expression::Assign { .. } | Expression::Let { .. } => Prec::Assign,
+        Expression::Conditional { .. } => Prec::Conditional,
+        Expression::Range { .. } => Prec::Range,
+        Expression::Coalesce { .. } => Prec::Coalesce,
+        Expression::Or { .. } => Prec::Or,
+        Expression::Xor { .. } => Prec::Xor,
+        Expression::And { .. } => Prec::And,
+        Expression::Compare { .. } | Expression::In { .. } => Prec::Compare,
+        Expression::Binary { op: BinOp::BitOr, .. } => Prec::BitOr,
+        Expression::Binary { op: BinOp::BitXor, .. } => Prec::BitXor,
+        Expression::Binary { op: BinOp::BitAnd, .. } => Prec::BitAnd,
+        Expression::Binary { op: BinOp::Shl, .. } | Expression::Binary { op: BinOp::Shr, .. } => Prec::Shift,
+        Expression::Binary { op: BinOp::Add, .. } | Expression::Binary { op: BinOp::Sub, .. } => Prec::Sum,
+        Expression::Binary { .. } => Prec::Term,
+        Expression::Not { .. } | Expression::Neg { .. } | Expression::BitNot { .. } | Expression::TypeOf { .. } => Prec::Unary,
+        Expression::Literal(Literal::Int(i)) if *i < 0 => Prec::Unary,
+        Expression::Literal(Literal::Float(x)) if x.is_sign_negative() => Prec::Unary,
+        _ => Prec::Postfix,
+    }
+}
+
+/// Writes `expr`, parenthesized if it binds more loosely than `min`.
+fn write_operand(expr: &Expression, min: Prec, out: &mut String) {
+    if prec(expr) < min {
+        out.push('(');
+        write_expr(expr, out);
+        out.push(')');
+    } else {
+        write_expr(expr, out);
+    }
+}
+
+/// Writes a left-associative operator: an equal-precedence rhs needs parentheses, an lhs doesn't.
+fn write_infix(lhs: &Expression, symbol: &str, rhs: &Expression, op_prec: Prec, out: &mut String) {
+    write_operand(lhs, op_prec, out);
+    write!(out, " {} ", symbol).unwrap();
+    let tighter = match op_prec {
+        Prec::Coalesce => Prec::Or,
+        Prec::Or => Prec::Xor,
+        Prec::Xor => Prec::And,
+        Prec::And => Prec::Compare,
+        Prec::Compare => Prec::BitOr,
+        Prec::BitOr => Prec::BitXor,
+        Prec::BitXor => Prec::BitAnd,
+        Prec::BitAnd => Prec::Shift,
+        Prec::Shift => Prec::Sum,
+        Prec::Sum => Prec::Term,
+        _ => Prec::Unary,
+    };
+    write_operand(rhs, tighter, out);
+}
+
+fn write_list(items: &[Expression], separator: &str, out: &mut String) {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push_str(separator);
+        }
+        write_expr(item, out);
+    }
+}
+
+fn write_expr(expr: &Expression, out: &mut String) {
+    match expr {
+        Expression::Dummy => out.push_str("<dummy>"),
+        Expression::Literal(literal) => write_literal(literal, out),
+        Expression::Identifier(name) => out.push_str(name),
+        Expression::Field { base, name } => {
+            write_operand(base, Prec::Postfix, out);
+            write!(out, ".{}", name).unwrap();
+        }
+        Expression::OptionalField { base, name } => {
+            write_operand(base, Prec::Postfix, out);
+            write!(out, "?.{}", name).unwrap();
+        }
+        Expression::Index { base, index } => {
+            write_operand(base, Prec::Postfix, out);
+            out.push('[');
+            write_expr(index, out);
+            out.push(']');
+        }
+        Expression::Binary { op, lhs, rhs } => write_infix(lhs, op.symbol(), rhs, prec(expr), out),
+        Expression::Compare { op, lhs, rhs } => write_infix(lhs, op.symbol(), rhs, Prec::Compare, out),
+        Expression::In { needle, haystack } => write_infix(needle, "in", haystack, Prec::Compare, out),
+        Expression::And { lhs, rhs } => write_infix(lhs, "&&", rhs, Prec::And, out),
+        Expression::Or { lhs, rhs } => write_infix(lhs, "||", rhs, Prec::Or, out),
+        Expression::Xor { lhs, rhs } => write_infix(lhs, "xor", rhs, Prec::Xor, out),
+        Expression::Coalesce { lhs, rhs } => write_infix(lhs, "??", rhs, Prec::Coalesce, out),
+        Expression::Not { operand } => {
+            out.push('!');
+            write_operand(operand, Prec::Unary, out);
+        }
+        Expression::Neg { operand } => {
+            out.push('-');
+            write_operand(operand, Prec::Unary, out);
+        }
+        Expression::BitNot { operand } => {
+            out.push('~');
+            write_operand(operand, Prec::Unary, out);
+        }
+        Expression::TypeOf { operand } => {
+            out.push_str("typeof ");
+            write_operand(operand, Prec::Unary, out);
+        }
+        Expression::Conditional { cond, then_branch, else_branch } => {
+            write_operand(cond, Prec::Range, out);
+            out.push_str(" ? ");
+            write_expr(then_branch, out);
+            out.push_str(" : ");
+            write_expr(else_branch, out);
+        }
+        Expression::If { cond, then_block, else_block } => {
+            out.push_str("if ");
+            write_expr(cond, out);
+            out.push_str(" then ");
+            write_expr(then_block, out);
+            if let Some(else_block) = else_block {
+                out.push_str(" else ");
+                write_expr(else_block, out);
+            }
+        }
+        Expression::Match { scrutinee, arms, default } => {
+            out.push_str("match ");
+            write_expr(scrutinee, out);
+            out.push_str(" { ");
+            for (pattern, body) in arms {
+                write_expr(pattern, out);
+                out.push_str(" => ");
+                write_expr(body, out);
+                out.push_str(", ");
+            }
+            if let Some(default) = default {
+                out.push_str("_ => ");
+                write_expr(default, out);
+                out.push(' ');
+            }
+            out.push('}');
+        }
+        Expression::While { cond, body } => {
+            out.push_str("while ");
+            write_expr(cond, out);
+            out.push_str(" do ");
+            write_expr(body, out);
+        }
+        Expression::ForIn { var, iterable, body } => {
+            write!(out, "for {} in ", var).unwrap();
+            write_expr(iterable, out);
+            out.push_str(" do ");
+            write_expr(body, out);
+        }
+        Expression::Range { start, end, inclusive } => {
+            // Ranges don't chain, so both ends must bind more tightly.
+            write_operand(start, Prec::Coalesce, out);
+            out.push_str(if *inclusive { "..=" } else { ".." });
+            write_operand(end, Prec::Coalesce, out);
+        }
+        Expression::List(items) => {
+            out.push('[');
+            write_list(items, ", ", out);
+            out.push(']');
+        }
+        Expression::Object(entries) if entries.is_empty() => out.push_str("{}"),
+        Expression::Object(entries) => {
+            out.push_str("{ ");
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                if is_identifier(key) {
+                    out.push_str(key);
+                } else {
+                    write_string(key, out);
+                }
+                out.push_str(": ");
+                write_expr(value, out);
+            }
+            out.push_str(" }");
+        }
+        Expression::Block(statements) if statements.is_empty() => out.push_str("{ null }"),
+        Expression::Block(statements) => {
+            out.push_str("{ ");
+            write_list(statements, "; ", out);
+            out.push_str(" }");
+        }
+        Expression::Defined { path } => {
+            out.push_str("defined(");
+            write_expr(path, out);
+            out.push(')');
+        }
+        Expression::Call { name, args } => {
+            write!(out, "{}(", name).unwrap();
+            write_list(args, ", ", out);
+            out.push(')');
+        }
+        Expression::Spanned(spanned) => write_expr(&spanned.node, out),
+        Expression::Assign { target, value } => {
+            write_expr(target, out);
+            out.push_str(" = ");
+            write_expr(value, out);
+        }
+        Expression::Let { name, value } => {
+            write!(out, "let {} = ", name).unwrap();
+            write_expr(value, out);
+        }
+    }
+}
+
+fn write_literal(literal: &Literal, out: &mut String) {
+    match literal {
+        Literal::Int(i) => write!(out, "{}", i).unwrap(),
+        // `Display` never uses an exponent, but drops the fraction of whole numbers.
+        Literal::Float(x) if x.fract() == 0.0 && x.is_finite() => write!(out, "{:.1}", x).unwrap(),
+        Literal::Float(x) => write!(out, "{}", x).unwrap(),
+        Literal::String(s) => write_string(s, out),
+        Literal::Bool(b) => write!(out, "{}", b).unwrap(),
+        Literal::Null => out.push_str("null"),
+    }
+}
+
+/// Whether `key` can be written bare as an object key, rather than quoted.
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    let starts_well = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    starts_well && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.contains(&key)
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{BinOp, Expression, Literal};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "1 + 2 * 3",
+            "(1 + 2) * 3",
+            "7 - (2 - 1)",
+            "7 - 2 - 1",
+            "a / (b * c) % d",
+            "-(x + 1) * -y",
+            "!!done",
+            "a || b xor c && d xor (e || f) xor !g",
+            "1 << n + 1 & m >> (2 >> 1) == (a & b) << 1",
+            "a | b ^ c & ~d == (a | b) ^ c & (d + 1) && (x & y) < z && a | (b | c)",
+            "typeof -x.y == \"int\" && typeof (a + b) != typeof { \"typeof\": 1 }",
+            "!(a && b) || c && (d || e)",
+            "(a < b) == (c >= 1.5)",
+            "a < b == c",
+            "ok ? x = 1 : y = 2",
+            "(a ? b : c) ? d : e ? f : g",
+            "player.gold = player.gold + quest.reward.gold",
+            "(x = 3) + 1",
+            "max(a, b = 2).c",
+            "defined(a?.b.c) || !defined(x)",
+            "(1 + 2).x",
+            "inventory[slot + 1].name[\"first\"]",
+            "(-1)[x = 0]",
+            "player?.quest?.stage.name ?? (a ? b : c)?.d[0]?.e",
+            "0..n + 1 ? (a..b)..=c : [1..=2 || x, (0..1)[0]]",
+            "a ?? b || c ?? (d ?? e) ? x ?? 0..y ?? 1 : (a ?? b).c",
+            "key in bag == (\"a\" in name) && { \"in\": 1, index: 2 }.index",
+            "{ x = 1; y = 2; x + y } * { }",
+            "{ let x = 1; (let y = x) + y }",
+            "[[], [1, \"a\", [x + 1,]], y]",
+            "{ name: \"Bob\", \"max hp\": 1, \"null\": {}, pos: { x: a ? b : c }, }.pos",
+            "{ \"let\": 1, \"xor\": 2, letter: 3 }",
+            "\"say \\\"hi\\\"\\n\\tC:\\\\ \\u{1}\"",
+            "\"\"\"\n    raw \"text\"\n    \"\"\" + 0x10 + 2.0",
+            "name # a comment\n == null",
+        ];
+        for src in sources.iter() {
+            let expr = parse(src).unwrap();
+            let printed = expr.to_source();
+            assert_eq!(parse(&printed).unwrap(), expr, "{} printed as {}", src, printed);
+            assert_eq!(parse(&printed).unwrap().to_source(), printed);
+        }
+
+        // Nodes without Talk syntax are the exception, and print as something that doesn't parse.
+        let e = |src: &str| Box::new(parse(src).unwrap());
+        let unparsable = [
+            Expression::If { cond: e("ok"), then_block: e("{ x = 1 }"), else_block: Some(e("2")) },
+            Expression::Match { scrutinee: e("x"), arms: vec![(*e("1"), *e("\"one\""))], default: None },
+            Expression::While { cond: e("x < 3"), body: e("{ x = x + 1 }") },
+            Expression::ForIn { var: "item".to_string(), iterable: e("items"), body: e("total = total + item") },
+        ];
+        for expr in unparsable.iter() {
+            let nested = Expression::List(vec![expr.clone()]);
+            for expr in [expr, &nested] {
+                assert!(parse(&expr.to_source()).is_err(), "{} parsed", expr.to_source());
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_source() {
+        assert_eq!(parse("((1 + (2 * 3)))").unwrap().to_source(), "1 + 2 * 3");
+        assert_eq!(parse("a-(b-c)").unwrap().to_source(), "a - (b - c)");
+        assert_eq!(parse("\"\"\"\n  \"Hi\"\n  \"\"\"").unwrap().to_source(), "\"\\\"Hi\\\"\\n\"");
+        assert_eq!(parse("{}").unwrap().to_source(), "{}");
+        assert_eq!(parse("{ \"hp\": 1, \"2x\": 2 }").unwrap().to_source(), "{ hp: 1, \"2x\": 2 }");
+        assert_eq!(parse("{ \"let\": 1, \"in\": 2 }").unwrap().to_source(), "{ \"let\": 1, \"in\": 2 }");
+        assert_eq!(Expression::Block(vec![]).to_source(), "{ null }");
+
+        let neg = Expression::Binary {
+            op: BinOp::Sub,
+            lhs: Box::new(Expression::Literal(Literal::Int(1))),
+            rhs: Box::new(Expression::Literal(Literal::Float(-2.0))),
+        };
+        assert_eq!(neg.to_source(), "1 - -2.0");
+        let field = Expression::Field { base: Box::new(Expression::Literal(Literal::Int(-1))), name: "x".to_string() };
+        assert_eq!(field.to_source(), "(-1).x");
+        let arms = vec![(parse("1").unwrap(), parse("\"one\"").unwrap())];
+        let matched = Expression::Match { scrutinee: Box::new(parse("x").unwrap()), arms, default: Some(Box::new(parse("null").unwrap())) };
+        assert_eq!(matched.to_source(), "match x { 1 => \"one\", _ => null }");
+    }
+}
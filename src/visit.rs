@@ -0,0 +1,279 @@
+//! Read-only traversal of the `Expression` tree, for analyses such as finding the fields a
+//! script depends on.
+//!
+//! Every `visit_*` method defaults to visiting the node's children, so a visitor overrides
+//! only the nodes it cares about. An override that still wants the children visited
+//! passes them to `visit_expression` itself.
+
+use std::collections::BTreeSet;
+
+use crate::eval::{BinOp, CmpOp, Expression, Literal, Span};
+
+pub trait Visitor {
+    fn visit_expression(&mut self, expr: &Expression) { walk_expression(self, expr) }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_identifier(&mut self, _name: &str) {}
+
+    fn visit_field(&mut self, base: &Expression, _name: &str) { self.visit_expression(base) }
+
+    fn visit_optional_field(&mut self, base: &Expression, _name: &str) { self.visit_expression(base) }
+
+    fn visit_index(&mut self, base: &Expression, index: &Expression) {
+        self.visit_expression(base);
+        self.visit_expression(index);
+    }
+
+    fn visit_binary(&mut self, _op: BinOp, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_compare(&mut self, _op: CmpOp, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_in(&mut self, needle: &Expression, haystack: &Expression) {
+        self.visit_expression(needle);
+        self.visit_expression(haystack);
+    }
+
+    fn visit_and(&mut self, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_or(&mut self, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_xor(&mut self, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_coalesce(&mut self, lhs: &Expression, rhs: &Expression) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_not(&mut self, operand: &Expression) { self.visit_expression(operand) }
+
+    fn visit_neg(&mut self, operand: &Expression) { self.visit_expression(operand) }
+
+    fn visit_bit_not(&mut self, operand: &Expression) { self.visit_expression(operand) }
+
+    fn visit_typeof(&mut self, operand: &Expression) { self.visit_expression(operand) }
+
+    /// Covers both `Conditional` and `If`; a missing `else` block is `None`.
+    fn visit_conditional(&mut self, cond: &Expression, then_branch: &Expression, else_branch: Option<&Expression>) {
+        self.visit_expression(cond);
+        self.visit_expression(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.visit_expression(else_branch);
+        }
+    }
+
+    fn visit_match(&mut self, scrutinee: &Expression, arms: &[(Expression, Expression)], default: Option<&Expression>) {
+        self.visit_expression(scrutinee);
+        for (pattern, body) in arms {
+            self.visit_expression(pattern);
+            self.visit_expression(body);
+        }
+        if let Some(default) = default {
+            self.visit_expression(default);
+        }
+    }
+
+    fn visit_while(&mut self, cond: &Expression, body: &Expression) {
+        self.visit_expression(cond);
+        self.visit_expression(body);
+    }
+
+    fn visit_for_in(&mut self, _var: &str, iterable: &Expression, body: &Expression) {
+        self.visit_expression(iterable);
+        self.visit_expression(body);
+    }
+
+    fn visit_range(&mut self, start: &Expression, end: &Expression, _inclusive: bool) {
+        self.visit_expression(start);
+        self.visit_expression(end);
+    }
+
+    fn visit_list(&mut self, items: &[Expression]) {
+        for item in items {
+            self.visit_expression(item);
+        }
+    }
+
+    fn visit_object(&mut self, entries: &[(String, Expression)]) {
+        for (_, value) in entries {
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[Expression]) {
+        for statement in statements {
+            self.visit_expression(statement);
+        }
+    }
+
+    fn visit_defined(&mut self, path: &Expression) { self.visit_expression(path) }
+
+    fn visit_call(&mut self, _name: &str, args: &[Expression]) {
+        for arg in args {
+            self.visit_expression(arg);
+        }
+    }
+
+    fn visit_spanned(&mut self, node: &Expression, _span: Span) { self.visit_expression(node) }
+
+    fn visit_assign(&mut self, target: &Expression, value: &Expression) {
+        self.visit_expression(target);
+        self.visit_expression(value);
+    }
+
+    fn visit_let(&mut self, _name: &str, value: &Expression) { self.visit_expression(value) }
+}
+
+/// Dispatches `expr` to the `visit_*` method for its kind.
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Dummy => {}
+        Expression::Literal(literal) => visitor.visit_literal(literal),
+        Expression::Identifier(name) => visitor.visit_identifier(name),
+        Expression::Field { base, name } => visitor.visit_field(base, name),
+        Expression::OptionalField { base, name } => visitor.visit_optional_field(base, name),
+        Expression::Index { base, index } => visitor.visit_index(base, index),
+        Expression::Binary { op, lhs, rhs } => visitor.visit_binary(*op, lhs, rhs),
+        Expression::Compare { op, lhs, rhs } => visitor.visit_compare(*op, lhs, rhs),
+        Expression::In { needle, haystack } => visitor.visit_in(needle, haystack),
+        Expression::And { lhs, rhs } => visitor.visit_and(lhs, rhs),
+        Expression::Or { lhs, rhs } => visitor.visit_or(lhs, rhs),
+        Expression::Xor { lhs, rhs } => visitor.visit_xor(lhs, rhs),
+        Expression::Coalesce { lhs, rhs } => visitor.visit_coalesce(lhs, rhs),
+        Expression::Not { operand } => visitor.visit_not(operand),
+        Expression::Neg { operand } => visitor.visit_neg(operand),
+        Expression::BitNot { operand } => visitor.visit_bit_not(operand),
+        Expression::TypeOf { operand } => visitor.visit_typeof(operand),
+        Expression::Conditional { cond, then_branch, else_branch } =>
+            visitor.visit_conditional(cond, then_branch, Some(else_branch)),
+        Expression::If { cond, then_block, else_block } =>
+            visitor.visit_conditional(cond, then_block, else_block.as_deref()),
+        Expression::Match { scrutinee, arms, default } => visitor.visit_match(scrutinee, arms, default.as_deref()),
+        Expression::While { cond, body } => visitor.visit_while(cond, body),
+        Expression::ForIn { var, iterable, body } => visitor.visit_for_in(var, iterable, body),
+        Expression::Range { start, end, inclusive } => visitor.visit_range(start, end, *inclusive),
+        Expression::List(items) => visitor.visit_list(items),
+        Expression::Object(entries) => visitor.visit_object(entries),
+        Expression::Block(statements) => visitor.visit_block(statements),
+        Expression::Defined { path } => visitor.visit_defined(path),
+        Expression::Call { name, args } => visitor.visit_call(name, args),
+        Expression::Spanned(spanned) => visitor.visit_spanned(&spanned.node, spanned.span),
+        Expression::Assign { target, value } => visitor.visit_assign(target, value),
+        Expression::Let { name, value } => visitor.visit_let(name, value),
+    }
+}
+
+/// Names of the context fields `expr` reads, not counting loop variables, `let` locals
+/// or fields it only assigns. For `a.b = c`, that is `a` and `c`.
+pub fn read_identifiers(expr: &Expression) -> BTreeSet<String> {
+    let mut reads = ReadIdentifiers { bound: Vec::new(), names: BTreeSet::new() };
+    reads.visit_expression(expr);
+    reads.names
+}
+
+struct ReadIdentifiers {
+    /// Loop variables and `let` locals in scope, innermost last.
+    bound: Vec<String>,
+    names: BTreeSet<String>,
+}
+
+impl Visitor for ReadIdentifiers {
+    fn visit_identifier(&mut self, name: &str) {
+        if !self.bound.iter().any(|bound| bound == name) {
+            self.names.insert(name.to_string());
+        }
+    }
+
+    fn visit_for_in(&mut self, var: &str, iterable: &Expression, body: &Expression) {
+        self.visit_expression(iterable);
+        self.bound.push(var.to_string());
+        self.visit_expression(body);
+        self.bound.pop();
+    }
+
+    fn visit_block(&mut self, statements: &[Expression]) {
+        // A `let` statement binds for the rest of the block. One nested deeper might
+        // not run, so a later read may still reach the field.
+        let outer = self.bound.len();
+        for statement in statements {
+            self.visit_expression(statement);
+            if let Expression::Let { name, .. } = statement {
+                self.bound.push(name.clone());
+            }
+        }
+        self.bound.truncate(outer);
+    }
+
+    fn visit_assign(&mut self, target: &Expression, value: &Expression) {
+        // The target alone is written, not read, so only the objects a field target goes
+        // through count.
+        if let Expression::Field { base, .. } = target {
+            self.visit_expression(base);
+        }
+        self.visit_expression(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{BinOp, Expression};
+    use crate::parser::parse;
+    use crate::visit::{read_identifiers, Visitor};
+
+    #[derive(Default)]
+    struct CountBinary(usize);
+
+    impl Visitor for CountBinary {
+        fn visit_binary(&mut self, _op: BinOp, lhs: &Expression, rhs: &Expression) {
+            self.0 += 1;
+            self.visit_expression(lhs);
+            self.visit_expression(rhs);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut count = CountBinary::default();
+        count.visit_expression(&parse("-(1 + 2) * x > 3 && max(a - 1, 2 % b) ? { y = 4 + 4; y } : 0").unwrap());
+        assert_eq!(count.0, 5);
+    }
+
+    #[test]
+    fn test_read_identifiers() {
+        let read = read_identifiers(&parse("player.gold = player.gold + quest.reward * bonus").unwrap());
+        assert_eq!(read.into_iter().collect::<Vec<_>>(), vec!["bonus", "player", "quest"]);
+
+        let read = read_identifiers(&parse("{ total = 0; len(items) > limit ? total : fallback }").unwrap());
+        assert_eq!(read.into_iter().collect::<Vec<_>>(), vec!["fallback", "items", "limit", "total"]);
+
+        let body = Box::new(Expression::Assign {
+            target: Box::new(Expression::Identifier("sum".to_string())),
+            value: Box::new(Expression::Binary {
+                op: BinOp::Add,
+                lhs: Box::new(Expression::Identifier("sum".to_string())),
+                rhs: Box::new(Expression::Identifier("x".to_string())),
+            }),
+        });
+        let expr = Expression::ForIn { var: "x".to_string(), iterable: Box::new(Expression::Identifier("list".to_string())), body };
+        assert_eq!(read_identifiers(&expr).into_iter().collect::<Vec<_>>(), vec!["list", "sum"]);
+
+        // A `let` statement hides the field for the rest of its block; a nested one might not run.
+        let read = read_identifiers(&parse("{ let cap = limit; cap > 0 ? (let hp = base) : hp } + cap").unwrap());
+        assert_eq!(read.into_iter().collect::<Vec<_>>(), vec!["base", "cap", "hp", "limit"]);
+    }
+}